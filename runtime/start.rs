@@ -4,7 +4,10 @@
     Compiles a Snek assembly file into a runtime Snek binary.
 */
 use std::env;
+use std::collections::HashSet;
 use std::convert::TryInto;
+use std::ffi::CStr;
+use std::os::raw::c_char;
 
 /// mismatch error code
 const MSMX_ERRCODE : i64 = 7; // msmx = mismatch
@@ -15,6 +18,42 @@ const OF_ERRCODE : i64 = 8;
 /// bounds error code
 const BND_ERRCODE : i64 = 9;
 
+/// arity error code (closure called with the wrong number of arguments)
+const ARITY_ERRCODE : i64 = 10;
+
+/// struct error code (record of another struct than the one expected)
+const STRUCT_ERRCODE : i64 = 11;
+
+/// match error code (no case of a match expression matched the value)
+const MATCH_ERRCODE : i64 = 12;
+
+/// division error code (division or remainder by zero)
+const DIV_ERRCODE : i64 = 13;
+
+/// global error code (global read before its initializer has run)
+const GLOBAL_ERRCODE : i64 = 14;
+
+/// raise error code (value raised outside of any try)
+const RAISE_ERRCODE : i64 = 15;
+
+/// exit status of a program stopped by a failed assertion or a panic
+const PANIC_STATUS : i32 = 3;
+
+/// mask of the tag bits of heap values
+const HEAP_TAG_MASK : u64 = 0b1111;
+
+/// tuple heap tag
+const TUPLE_TAG : u64 = 0b0001;
+
+/// closure heap tag
+const CLOSURE_TAG : u64 = 0b0101;
+
+/// string heap tag
+const STRING_TAG : u64 = 0b1001;
+
+/// record heap tag
+const RECORD_TAG : u64 = 0b1101;
+
 /// true  value representation (code + tag)
 const TRUE_VAL  : i64 = 7;
 
@@ -34,26 +73,50 @@ extern "C" {
     // it does not add an underscore in front of the name.
     // Courtesy of Max New (https://maxsnew.com/teaching/eecs-483-fa22/hw_adder_assignment.html)
     #[link_name = "\x01our_code_starts_here"]
-    fn our_code_starts_here(input: u64, memory: *mut u64) -> u64;
+    fn our_code_starts_here(input: u64, memory: *mut u64, args: u64) -> u64;
 }
 
 /// Exported external C function for the runtime environment
-/// that reports an error and aborts the process
+/// that reports an error at a source location (line << 32 | col) and aborts the process. The value
+/// is only set for a raise that nothing caught.
 #[no_mangle]
 #[export_name = "\x01snek_error"]
-pub extern "C" fn snek_error(errcode: i64) {
-    // Print error message according to error code
+pub extern "C" fn snek_error(errcode: i64, loc: u64, file: *const c_char, val: u64) {
+    // Print error message with its stable code (see `diamondback --explain <code>`)
     match errcode {
-        MSMX_ERRCODE => { eprintln!("Operation with invalid argument(s)"); }
-        OF_ERRCODE => { eprintln!("Operation caused arithmetic overflow"); }
-        BND_ERRCODE => { eprintln!("Operation with index out of bounds"); }
-        _ => { eprintln!("An error occurred {errcode}"); }
+        MSMX_ERRCODE => { eprintln!("error[R0001]: operation with invalid argument(s)"); }
+        OF_ERRCODE => { eprintln!("error[R0002]: operation caused arithmetic overflow"); }
+        BND_ERRCODE => { eprintln!("error[R0003]: operation with index out of bounds"); }
+        ARITY_ERRCODE => { eprintln!("error[R0004]: function called with the wrong number of arguments"); }
+        STRUCT_ERRCODE => { eprintln!("error[R0005]: record of the wrong struct passed to a field operation"); }
+        MATCH_ERRCODE => { eprintln!("error[R0006]: no case of match expression matched the value"); }
+        DIV_ERRCODE => { eprintln!("error[R0007]: division by zero"); }
+        GLOBAL_ERRCODE => { eprintln!("error[R0008]: global read before its initializer has run"); }
+        RAISE_ERRCODE => { eprintln!("error[R0009]: uncaught exception: {}", snek_string(val)); }
+        _ => { eprintln!("error: unknown runtime error {errcode}"); }
     }
 
+    // Print the source location of the failing operation
+    let file = unsafe { CStr::from_ptr(file) }.to_string_lossy();
+    eprintln!("  --> {}:{}:{}", file, loc >> 32, loc & 0xffffffff);
+
     // Abort the process immediately
     std::process::exit(1);
 }
 
+/// Exported external C function for the runtime environment
+/// that reports a failed assertion or a panic with its message, the source text of the failing
+/// expression and its location (line << 32 | col), and exits with PANIC_STATUS
+#[no_mangle]
+#[export_name = "\x01snek_panic"]
+pub extern "C" fn snek_panic(msg: u64, text: u64, loc: u64, file: *const c_char) {
+    let file = unsafe { CStr::from_ptr(file) }.to_string_lossy();
+    eprintln!("panic: {}", snek_string(msg));
+    eprintln!("  --> {}:{}:{}", file, loc >> 32, loc & 0xffffffff);
+    eprintln!("  in {}", snek_string(text));
+    std::process::exit(PANIC_STATUS);
+}
+
 /// Exported external C function for the runtime environment
 /// that prints out the String representation of an internal value
 #[no_mangle]
@@ -70,9 +133,26 @@ pub fn snek_string(val: u64) -> String {
     match val as i64 {
         FALSE_VAL => String::from("false"),
         TRUE_VAL => String::from("true"),
-        _ if val % 4 == 1 => {
+        _ if val & HEAP_TAG_MASK == CLOSURE_TAG => String::from("<function>"),
+        _ if val & HEAP_TAG_MASK == STRING_TAG => String::from_utf8_lossy(unsafe { string_bytes(val) }).into_owned(),
+        _ if val & HEAP_TAG_MASK == RECORD_TAG => {
+            // The descriptor holds the number of fields, then the struct and field names
+            let ptr = (val - RECORD_TAG) as *const u64;
+            let desc = unsafe { *ptr } as *const u64;
+            let len = unsafe { *desc };
+            let mut name = unsafe { desc.offset(1) } as *const c_char;
+            let struct_name = unsafe { CStr::from_ptr(name) };
+            let mut fields : Vec<String> = Vec::new();
+            for index in 1..=len {
+                name = unsafe { name.add(CStr::from_ptr(name).to_bytes().len() + 1) };
+                let field = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+                fields.push(format!("{field}: {}", snek_string(unsafe { *ptr.offset(index.try_into().unwrap()) })));
+            }
+            format!("{}{{{}}}", struct_name.to_string_lossy(), fields.join(", "))
+        }
+        _ if val & HEAP_TAG_MASK == TUPLE_TAG => {
             let mut output = String::new();
-            let ptr = (val - 1) as *const u64;
+            let ptr = (val - TUPLE_TAG) as *const u64;
             let len = unsafe { *ptr };
             if len > 0 {
                 for index in 1..len {
@@ -87,6 +167,134 @@ pub fn snek_string(val: u64) -> String {
     }
 }
 
+/// Returns the bytes of a string value, stored after its length
+unsafe fn string_bytes<'a>(val: u64) -> &'a [u8] {
+    let ptr = (val - STRING_TAG) as *const u64;
+    std::slice::from_raw_parts(ptr.offset(1) as *const u8, *ptr as usize)
+}
+
+/// Writes a string object (its length followed by its bytes) at the heap pointer, returning its
+/// value. The compiled code moves the heap pointer past it.
+unsafe fn alloc_string(bytes: &[u8], heap: *mut u64) -> u64 {
+    *heap = bytes.len() as u64;
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), heap.offset(1) as *mut u8, bytes.len());
+    heap as u64 + STRING_TAG
+}
+
+/// Exported external C function for the runtime environment
+/// that allocates the decimal representation of a number
+#[export_name = "\x01snek_number_to_string"]
+pub extern "C" fn snek_number_to_string(val: u64, heap: *mut u64) -> u64 {
+    unsafe { alloc_string(((val as i64) >> 1).to_string().as_bytes(), heap) }
+}
+
+/// Exported external C function for the runtime environment
+/// that parses a string as a number, returning false if it is not a Snek integer
+#[export_name = "\x01snek_string_to_number"]
+pub extern "C" fn snek_string_to_number(val: u64) -> u64 {
+    let text = String::from_utf8_lossy(unsafe { string_bytes(val) });
+    match text.parse::<i64>() {
+        Ok(n) if (-LIM..LIM).contains(&n) => (n * 2) as u64,
+        _ => FALSE_VAL as u64,
+    }
+}
+
+/// Exported external C function for the runtime environment
+/// that allocates the concatenation of two strings
+#[export_name = "\x01snek_string_append"]
+pub extern "C" fn snek_string_append(val1: u64, val2: u64, heap: *mut u64) -> u64 {
+    let bytes = unsafe { [string_bytes(val1), string_bytes(val2)].concat() };
+    unsafe { alloc_string(&bytes, heap) }
+}
+
+/// Exported external C function for the runtime environment
+/// that compares the contents of two strings
+#[export_name = "\x01snek_string_equal"]
+pub extern "C" fn snek_string_equal(val1: u64, val2: u64) -> u64 {
+    if unsafe { string_bytes(val1) == string_bytes(val2) } { TRUE_VAL as u64 } else { FALSE_VAL as u64 }
+}
+
+/// Exported external C function for the runtime environment
+/// that compares two values structurally (equal?)
+#[export_name = "\x01snek_equal"]
+pub extern "C" fn snek_equal(val1: u64, val2: u64) -> u64 {
+    if equal(val1, val2, &mut HashSet::new()) { TRUE_VAL as u64 } else { FALSE_VAL as u64 }
+}
+
+/// Compares two values by contents: numbers and booleans by value, strings by their bytes,
+/// tuples and records (of the same struct) element by element, and functions by identity.
+/// Pairs of heap values already being compared are assumed equal, so that comparing tuples
+/// that contain themselves terminates.
+fn equal(val1: u64, val2: u64, seen: &mut HashSet<(u64, u64)>) -> bool {
+    if val1 == val2 {
+        return true;
+    }
+    // Heap values are the only ones that can be equal without being the same word
+    let is_heap = |val: u64| val & 0b11 == 0b01;
+    let tag = val1 & HEAP_TAG_MASK;
+    if !is_heap(val1) || tag != val2 & HEAP_TAG_MASK {
+        return false;
+    }
+    if !seen.insert((val1, val2)) {
+        return true;
+    }
+    let (ptr1, ptr2) = ((val1 - tag) as *const u64, (val2 - tag) as *const u64);
+    match tag {
+        STRING_TAG => unsafe { string_bytes(val1) == string_bytes(val2) },
+        // A tuple starts with its length and a record with its struct descriptor,
+        // which holds the number of fields
+        TUPLE_TAG | RECORD_TAG => unsafe {
+            let (head1, head2) = (*ptr1, *ptr2);
+            if head1 != head2 {
+                return false;
+            }
+            let len = if tag == TUPLE_TAG { head1 } else { *(head1 as *const u64) };
+            (1..=len as usize).all(|i| equal(*ptr1.add(i), *ptr2.add(i), seen))
+        },
+        _ => false,
+    }
+}
+
+/// Exported external C function for the runtime environment
+/// that allocates the bytes of a string between two indices (already bounds checked)
+#[export_name = "\x01snek_substring"]
+pub extern "C" fn snek_substring(val: u64, start: u64, end: u64, heap: *mut u64) -> u64 {
+    let bytes = unsafe { string_bytes(val) };
+    let range = ((start as i64) >> 1) as usize..((end as i64) >> 1) as usize;
+    unsafe { alloc_string(&bytes[range], heap) }
+}
+
+/// Returns the elements of a tuple value, stored after its length
+unsafe fn tuple_elements<'a>(val: u64) -> &'a [u64] {
+    let ptr = (val - TUPLE_TAG) as *const u64;
+    std::slice::from_raw_parts(ptr.offset(1), *ptr as usize)
+}
+
+/// Writes a tuple object (its length followed by its elements) at the heap pointer, returning its
+/// value. The compiled code moves the heap pointer past it.
+unsafe fn alloc_tuple(elements: &[u64], heap: *mut u64) -> u64 {
+    *heap = elements.len() as u64;
+    std::ptr::copy_nonoverlapping(elements.as_ptr(), heap.offset(1), elements.len());
+    heap as u64 + TUPLE_TAG
+}
+
+/// Exported external C function for the runtime environment
+/// that allocates the elements of a tuple between two (checked) tagged indices
+#[export_name = "\x01snek_tuple_slice"]
+pub extern "C" fn snek_tuple_slice(val: u64, start: u64, end: u64, heap: *mut u64) -> u64 {
+    let elements = unsafe { tuple_elements(val) };
+    let range = ((start as i64) >> 1) as usize..((end as i64) >> 1) as usize;
+    unsafe { alloc_tuple(&elements[range], heap) }
+}
+
+/// Exported external C function for the runtime environment
+/// that allocates the concatenation of two tuples
+#[export_name = "\x01snek_tuple_append"]
+pub extern "C" fn snek_tuple_append(val1: u64, val2: u64, heap: *mut u64) -> u64 {
+    let elements = unsafe { [tuple_elements(val1), tuple_elements(val2)].concat() };
+    unsafe { alloc_tuple(&elements, heap) }
+}
+
 /// Parses input string into an internal value representation
 fn parse_input(input: &str) -> u64 {
     // Match input to the various possible String representations
@@ -113,20 +321,23 @@ fn parse_input(input: &str) -> u64 {
     
 // }
 
-/// Collects input argument and runs the snek binary, printing
+/// Collects the input arguments and runs the snek binary, printing
 /// the output
 fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    // set input to first argument
-    // if first argument is non-existent, set input to false
-    let input = if args.len() == 2 { &args[1] } else { "false" };
-    let input = parse_input(&input);
+    // parse every argument
+    // set input to the first argument, or to false if there is none
+    let args: Vec<u64> = env::args().skip(1).map(|arg| parse_input(&arg)).collect();
+    let input = args.first().copied().unwrap_or(FALSE_VAL as u64);
 
-    // allocate heap memory
+    // allocate heap memory, aligned for heap objects
     let mut memory = Vec::<u64>::with_capacity(HEAP_SIZE);
     let heap : *mut u64 = memory.as_mut_ptr();// mem_allocate(HEAP_SIZE);
+    let heap = unsafe { heap.add(heap.align_offset(16)) };
+
+    // the tuple of the arguments is the first heap object
+    let args_val = unsafe { alloc_tuple(&args, heap) };
+    let heap = unsafe { heap.add((args.len() + 2) / 2 * 2) };
 
-    let i: u64 = unsafe { our_code_starts_here(input, heap) };
+    let i: u64 = unsafe { our_code_starts_here(input, heap, args_val) };
     snek_print(i);
 }
@@ -11,23 +11,28 @@ use crate::utils::*;
 use im::HashMap;
 
 /// Compiles Expr expression to a corresponding vector of instructions
-fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
+fn compile_expr(e: &RExpr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
     // Initialize instruction vector
     let mut instrs : Vec<Instr> = Vec::new();
 
     // Match expression enum
-    match e {
+    match &e.kind {
         // Integer value representation into rax
-        Expr::Number(n) => { instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm((*n)*2))); },
+        RExprKind::Number(n) => { instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm((*n)*2))); },
         // Boolean value representation into rax
-        Expr::Boolean(b) => {
+        RExprKind::Boolean(b) => {
             instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm(if *b { TRUE_VAL } else { FALSE_VAL })));
         },
+        // String literal in the data section, tagged into rax
+        RExprKind::Str(id) => {
+            instrs.push(Instr::Lea(Val::Reg(Reg::RAX), Val::LabelAddr(format!("snek_str_{id}"))));
+            instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm(STRING_TAG)));
+        },
         // Tuple value representation into rax
-        Expr::Tuple(vec) => {
+        RExprKind::Tuple(vec) => {
             // Allocate tuple on the heap
             instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::Reg(Reg::R15)));
-            instrs.push(Instr::Add(Val::Reg(Reg::R15), Val::Imm(((vec.len() as i32+1)*WORD_SIZE).into())));
+            instrs.push(Instr::Add(Val::Reg(Reg::R15), Val::Imm(heap_size(vec.len() + 1).into())));
 
             // Evaluate and place elements in tuple
             let mut offset = 0;
@@ -44,31 +49,28 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
             
             // Return tuple representation
             instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
-            instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm(1)));
-        },
-        // "input" identifier value in rdi moved to rax
-        Expr::Id(s) if s == "input" => {
-            if ctxt.in_func {
-                panic!("\'input\' cannot be used within function");
-            }
-            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Reg(Reg::RDI))); 
+            instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm(TUPLE_TAG)));
         },
+        // "input" global value in the data section moved to rax
+        RExprKind::Input => { instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::LabelAddr(String::from("snek_input")))); },
+        // Tuple of the command-line arguments in the data section moved to rax
+        RExprKind::Args => { instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::LabelAddr(String::from("snek_args")))); },
         // Identifier value on stack moved to rax
-        Expr::Id(s)     => {
-            if let Some(loc) = ctxt.env.get(s) {
-                instrs.push(Instr::Mov(Val::Reg(Reg::RAX), loc.value()));
-            } else {
-                panic!("Unbound variable identifier {}", s); // unbound variable error
-            }
+        RExprKind::Var(id) => { instrs.push(Instr::Mov(Val::Reg(Reg::RAX), ctxt.env[id].value())); },
+        // Global value in the data section moved to rax. A function called by an initializer may
+        // read a global that has not been set yet.
+        RExprKind::Global(gid) => {
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::LabelAddr(format!("snek_global_{gid}"))));
+            instrs.append(&mut check_global_set(&e.span));
         },
         // Unary operation performed and result moved to rax
-        Expr::UnOp(op, e) => {
+        RExprKind::UnOp(op, e) => {
             // Compile inner expression into rax
             instrs.append(&mut compile_expr(e, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
 
             // If arithmetic, check for mismatch error
             if op.get_type() == Op1Type::Arithmetic {
-                instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, lbl));
+                instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, &e.span, lbl));
             }
 
             // Match unary operator and perform relevant instructions
@@ -77,12 +79,12 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
                 // add1 (+= 1)
                 Op1::Add1 => {
                     instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm(2)));
-                    instrs.append(&mut check_of()); // Check for overflow
+                    instrs.append(&mut check_of(&e.span)); // Check for overflow
                 },
                 // sub1 (-= 1)
                 Op1::Sub1 => {
                     instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(2)));
-                    instrs.append(&mut check_of()); // Check for overflow
+                    instrs.append(&mut check_of(&e.span)); // Check for overflow
                 },
                 // isnum (whether it is an integer or not)
                 Op1::IsNum => { 
@@ -110,10 +112,64 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
                     instrs.push(Instr::Pop(Val::Reg(Reg::RDI)));
                     instrs.push(Instr::Add(Val::Reg(Reg::RSP), Val::Imm(offset as i64)));
                 },
+                // string-length (number of bytes, stored untagged before the contents)
+                Op1::StringLength => {
+                    instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::String, &e.span, lbl));
+                    instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(STRING_TAG)));
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RAX, 0)));
+                    instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Reg(Reg::RAX)));
+                },
+                // number->string (allocates the decimal representation on the heap)
+                Op1::NumberToString => {
+                    instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, &e.span, lbl));
+                    instrs.append(&mut compile_runtime_call("snek_number_to_string", &[Val::Reg(Reg::RAX), Val::Reg(Reg::R15)], ctxt.si));
+                    instrs.append(&mut bump_string_heap());
+                },
+                // bitnot (complements the value, then clears the tag bit set by the complement)
+                Op1::BitNot => {
+                    instrs.push(Instr::Not(Val::Reg(Reg::RAX)));
+                    instrs.push(Instr::Xor(Val::Reg(Reg::RAX), Val::Imm(1)));
+                },
+                // not (flips the boolean bit distinguishing true from false)
+                Op1::Not => {
+                    instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Boolean, &e.span, lbl));
+                    instrs.push(Instr::Xor(Val::Reg(Reg::RAX), Val::Imm(TRUE_VAL ^ FALSE_VAL)));
+                },
+                // string->number (false if the string is not a Snek integer)
+                Op1::StringToNumber => {
+                    instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::String, &e.span, lbl));
+                    instrs.append(&mut compile_runtime_call("snek_string_to_number", &[Val::Reg(Reg::RAX)], ctxt.si));
+                },
+                // tlen (number of elements, stored untagged before the elements)
+                Op1::TupleLength => {
+                    instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Tuple, &e.span, lbl));
+                    instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(TUPLE_TAG)));
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RAX, 0)));
+                    instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Reg(Reg::RAX)));
+                },
+                // istuple (whether the heap tag is the tuple tag or not)
+                Op1::IsTuple => {
+                    instrs.push(Instr::And(Val::Reg(Reg::RAX), Val::Imm(HEAP_TAG_MASK)));
+                    instrs.push(Instr::Cmp(Val::Reg(Reg::RAX), Val::Imm(TUPLE_TAG)));
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::Imm(TRUE_VAL)));
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm(FALSE_VAL)));
+                    instrs.push(Instr::CMove(Val::Reg(Reg::RAX), Val::Reg(Reg::RBX)));
+                },
+                // tcopy (slices the whole tuple into a fresh allocation)
+                Op1::TupleCopy => {
+                    instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Tuple, &e.span, lbl));
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::Reg(Reg::RAX)));
+                    instrs.push(Instr::Sub(Val::Reg(Reg::RBX), Val::Imm(TUPLE_TAG)));
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RBX, 0)));
+                    instrs.push(Instr::Add(Val::Reg(Reg::RBX), Val::Reg(Reg::RBX)));
+                    let args = [Val::Reg(Reg::RAX), Val::Imm(0), Val::Reg(Reg::RBX), Val::Reg(Reg::R15)];
+                    instrs.append(&mut compile_runtime_call("snek_tuple_slice", &args, ctxt.si));
+                    instrs.append(&mut bump_tuple_heap());
+                },
             }
         },
         // Binary operation performed and result moved to rax
-        Expr::BinOp(op, e1, e2) => {
+        RExprKind::BinOp(op, e1, e2) => {
             // Compile inner expression 2 and push to stack
             instrs.append(&mut compile_expr(e2, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
             instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::Reg(Reg::RAX)));
@@ -124,13 +180,25 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
             if op.get_type() == Op2Type::Equality {
                 // Type check instructions with mismatch check
                 instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), 
-                        Some(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)), ValCheck::Equality, lbl));
+                        Some(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)), ValCheck::Equality, &e.span, lbl));
+            // If string operation, check if both types are strings
+            } else if op.get_type() == Op2Type::String {
+                instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::String, &e.span, lbl));
+                instrs.append(&mut check_msmx(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), None,
+                        ValCheck::String, &e.span, lbl));
+            // Structural equality compares values of any types
+            } else if op.get_type() == Op2Type::Structural {
+            // If tuple operation, check if both types are tuples
+            } else if op.get_type() == Op2Type::Tuple {
+                instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Tuple, &e.span, lbl));
+                instrs.append(&mut check_msmx(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), None,
+                        ValCheck::Tuple, &e.span, lbl));
             // Otherwise the binary operation is arithmetic, and 
             // we check if both types are numbers
             } else {
-                instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, lbl));
+                instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, &e.span, lbl));
                 instrs.append(&mut check_msmx(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), None,
-                        ValCheck::Integer, lbl));
+                        ValCheck::Integer, &e.span, lbl));
             }
 
             // Match binary operator
@@ -138,18 +206,76 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
                 // +
                 Op2::Plus  => { 
                     instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
-                    instrs.append(&mut check_of()); // Check for overflow
+                    instrs.append(&mut check_of(&e.span)); // Check for overflow
                 },
                 // -
                 Op2::Minus => { 
                     instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
-                    instrs.append(&mut check_of()); // Check for overflow
+                    instrs.append(&mut check_of(&e.span)); // Check for overflow
                 },
                 // *
                 Op2::Times => {
                     instrs.push(Instr::Sar(Val::Reg(Reg::RAX), Val::Imm(1)));
                     instrs.push(Instr::IMul(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
-                    instrs.append(&mut check_of()); // Check for overflow
+                    instrs.append(&mut check_of(&e.span)); // Check for overflow
+                },
+                // /, quotient, remainder and modulo (idiv on the untagged values)
+                Op2::Divide | Op2::Quotient | Op2::Remainder | Op2::Modulo => {
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RCX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
+                    instrs.append(&mut check_div_zero(Val::Reg(Reg::RCX), &e.span));
+                    instrs.push(Instr::Sar(Val::Reg(Reg::RAX), Val::Imm(1)));
+                    instrs.push(Instr::Sar(Val::Reg(Reg::RCX), Val::Imm(1)));
+                    instrs.push(Instr::Cqo);
+                    instrs.push(Instr::IDiv(Val::Reg(Reg::RCX)));
+
+                    // idiv truncates: / and modulo round towards negative infinity instead, which
+                    // differs when the remainder is not zero and has the other sign than the divisor
+                    if matches!(op, Op2::Divide | Op2::Modulo) {
+                        let floor_lbl = format!("floor_{}", *lbl);
+                        *lbl += 1;
+                        instrs.push(Instr::Test(Val::Reg(Reg::RDX), Val::Reg(Reg::RDX)));
+                        instrs.push(Instr::Je(Val::Label(floor_lbl.clone())));
+                        instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::Reg(Reg::RDX)));
+                        instrs.push(Instr::Xor(Val::Reg(Reg::RBX), Val::Reg(Reg::RCX)));
+                        instrs.push(Instr::Jge(Val::Label(floor_lbl.clone())));
+                        instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(1)));
+                        instrs.push(Instr::Add(Val::Reg(Reg::RDX), Val::Reg(Reg::RCX)));
+                        instrs.push(Instr::Label(Val::Label(floor_lbl)));
+                    }
+                    if matches!(op, Op2::Remainder | Op2::Modulo) {
+                        instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Reg(Reg::RDX)));
+                    }
+
+                    // Tag the result, which overflows for the lowest integer divided by -1
+                    instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Reg(Reg::RAX)));
+                    instrs.append(&mut check_of(&e.span));
+                },
+                // bitand, bitor and bitxor (the tag bits of both operands are zero)
+                Op2::BitAnd => { instrs.push(Instr::And(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE))); },
+                Op2::BitOr => { instrs.push(Instr::Or(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE))); },
+                Op2::BitXor => { instrs.push(Instr::Xor(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE))); },
+                // shift-left and shift-right (arithmetic) by a count that is not negative,
+                // capped at 63 since larger counts do not change the result further
+                Op2::ShiftLeft | Op2::ShiftRight => {
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RCX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
+                    instrs.push(Instr::Sar(Val::Reg(Reg::RCX), Val::Imm(1)));
+                    instrs.append(&mut check_shift_count(Val::Reg(Reg::RCX), &e.span));
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::Imm(63)));
+                    instrs.push(Instr::Cmp(Val::Reg(Reg::RCX), Val::Reg(Reg::RBX)));
+                    instrs.push(Instr::CMovg(Val::Reg(Reg::RCX), Val::Reg(Reg::RBX)));
+                    if matches!(op, Op2::ShiftLeft) {
+                        // Overflows if shifting back does not give the original value
+                        instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::Reg(Reg::RAX)));
+                        instrs.push(Instr::Shl(Val::Reg(Reg::RAX), Val::Reg(Reg::CL)));
+                        instrs.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Reg(Reg::RAX)));
+                        instrs.push(Instr::Sar(Val::Reg(Reg::RDX), Val::Reg(Reg::CL)));
+                        instrs.push(Instr::Cmp(Val::Reg(Reg::RDX), Val::Reg(Reg::RBX)));
+                        instrs.append(&mut check_of_ne(&e.span));
+                    } else {
+                        // Bits shifted into the tag are cleared
+                        instrs.push(Instr::Sar(Val::Reg(Reg::RAX), Val::Reg(Reg::CL)));
+                        instrs.push(Instr::And(Val::Reg(Reg::RAX), Val::Imm(-2)));
+                    }
                 },
                 // <, less than
                 Op2::Lt => {
@@ -186,11 +312,33 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
                     instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::Imm(TRUE_VAL)));
                     instrs.push(Instr::CMove(Val::Reg(Reg::RAX), Val::Reg(Reg::RBX)));
                 },
+                // string-append (allocates the concatenation on the heap)
+                Op2::StringAppend => {
+                    let args = [Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::Reg(Reg::R15)];
+                    instrs.append(&mut compile_runtime_call("snek_string_append", &args, ctxt.si + 1));
+                    instrs.append(&mut bump_string_heap());
+                },
+                // tappend, allocated by the runtime
+                Op2::TupleAppend => {
+                    let args = [Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::Reg(Reg::R15)];
+                    instrs.append(&mut compile_runtime_call("snek_tuple_append", &args, ctxt.si + 1));
+                    instrs.append(&mut bump_tuple_heap());
+                },
+                // equal?, equal contents compared recursively by the runtime
+                Op2::StructuralEqual => {
+                    let args = [Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)];
+                    instrs.append(&mut compile_runtime_call("snek_equal", &args, ctxt.si + 1));
+                },
+                // string=?, equal contents
+                Op2::StringEqual => {
+                    let args = [Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)];
+                    instrs.append(&mut compile_runtime_call("snek_string_equal", &args, ctxt.si + 1));
+                },
             }
         },
         // Let bindings evaluated and pushed on stack, and used for main expression
         // Value moved into rax
-        Expr::Let(binds, e) => {
+        RExprKind::Let(binds, e) => {
             // Build new inner environment
             let mut new_env = ctxt.env.clone();
             let mut sii     = ctxt.si;
@@ -199,15 +347,42 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
                 let loc : LocPtr = LocPtr::LStack(-sii*WORD_SIZE);
                 instrs.append(&mut compile_expr(expr, ExprContext { si: sii, env: &new_env, tail: TailContext::Invalid, ..ctxt }, lbl));
                 instrs.push(Instr::Mov(loc.value(), Val::Reg(Reg::RAX)));
-                new_env = new_env.update(id.to_string(), loc);
+                new_env = new_env.update(*id, loc);
                 sii += 1;
             }
             // Evaluate final expression with the new environment
             instrs.append(&mut compile_expr(e, ExprContext { si: sii, env: &new_env, ..ctxt }, lbl));
         },
+        // Local functions each get a closure on the stack, like let bindings. The closures are
+        // allocated first and then given the closures of the group they capture, so that the
+        // functions can call each other.
+        RExprKind::LetRec(funcs, e) => {
+            let mut new_env = ctxt.env.clone();
+            let mut sii     = ctxt.si;
+            for (id, _) in funcs {
+                new_env = new_env.update(*id, LocPtr::LStack(-sii*WORD_SIZE));
+                sii += 1;
+            }
+            let in_group = |id: &BindId| funcs.iter().any(|(fid, _)| fid == id);
+            for (id, lid) in funcs {
+                let lambda = &ctxt.lambdas[*lid];
+                let captures : Vec<Val> = lambda.captures.iter()
+                    .map(|capture| if in_group(capture) { Val::Imm(0) } else { new_env[capture].value() }).collect();
+                instrs.append(&mut compile_lambda_closure(*lid, lambda, &captures));
+                instrs.push(Instr::Mov(new_env[id].value(), Val::Reg(Reg::RAX)));
+            }
+            for (id, lid) in funcs {
+                for (offset, capture) in (2..).zip(ctxt.lambdas[*lid].captures.iter()).filter(|(_, capture)| in_group(capture)) {
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RAX), new_env[id].value()));
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RBX), new_env[capture].value()));
+                    instrs.push(Instr::Mov(Val::MemPtr(Reg::RAX, offset*WORD_SIZE - CLOSURE_TAG as i32), Val::Reg(Reg::RBX)));
+                }
+            }
+            instrs.append(&mut compile_expr(e, ExprContext { si: sii, env: &new_env, ..ctxt }, lbl));
+        },
         // If condition is true, first expression moved to rax
         // Otherwise, second expression moved to rax
-        Expr::If(cond_e, e1, e2) => {
+        RExprKind::If(cond_e, e1, e2) => {
             let block_num = *lbl;
             *lbl += 1;
             let else_lbl = format!("else_{}", block_num);
@@ -221,10 +396,44 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
             instrs.append(&mut compile_expr(e2, ctxt, lbl));
             instrs.push(Instr::Label(Val::Label(endif_lbl.clone())));
         },
+        // Evaluate the second operand only if the first does not decide the result. Both operands are
+        // checked to be booleans, except for the second one in tail position, whose value is returned
+        // unchecked so that a call there is a tail call.
+        RExprKind::And(e1, e2) | RExprKind::Or(e1, e2) => {
+            let end_lbl = format!("endlogic_{}", *lbl);
+            *lbl += 1;
+            let short_val = if matches!(e.kind, RExprKind::And(..)) { FALSE_VAL } else { TRUE_VAL };
+            instrs.append(&mut compile_expr(e1, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Boolean, &e.span, lbl));
+            instrs.push(Instr::Cmp(Val::Reg(Reg::RAX), Val::Imm(short_val)));
+            instrs.push(Instr::Je(Val::Label(end_lbl.clone())));
+            instrs.append(&mut compile_expr(e2, ctxt, lbl));
+            if ctxt.tail != TailContext::Valid {
+                instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Boolean, &e.span, lbl));
+            }
+            instrs.push(Instr::Label(Val::Label(end_lbl)));
+        },
+        // Evaluate the body of the first clause whose test is not false, like nested ifs
+        RExprKind::Cond(clauses, e_else) => {
+            let cond_num = *lbl;
+            *lbl += 1;
+            let end_lbl = format!("endcond_{cond_num}");
+            for (clause_num, (test, body)) in clauses.iter().enumerate() {
+                let next_lbl = format!("cond_{cond_num}_{}", clause_num + 1);
+                instrs.append(&mut compile_expr(test, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+                instrs.push(Instr::Cmp(Val::Reg(Reg::RAX), Val::Imm(FALSE_VAL)));
+                instrs.push(Instr::Je(Val::Label(next_lbl.clone())));
+                instrs.append(&mut compile_expr(body, ctxt, lbl));
+                instrs.push(Instr::Jmp(Val::Label(end_lbl.clone())));
+                instrs.push(Instr::Label(Val::Label(next_lbl)));
+            }
+            instrs.append(&mut compile_expr(e_else, ctxt, lbl));
+            instrs.push(Instr::Label(Val::Label(end_lbl)));
+        },
         // Loop the inner expression infinitely
-        Expr::Loop(e) => {
+        RExprKind::Loop(e) => {
             let tail_ctxt = if ctxt.tail == TailContext::Valid { TailContext::Loop } else { TailContext::Invalid };
-            let new_ctxt = ExprContext { loop_num: *lbl, tail: tail_ctxt, ..ctxt };
+            let new_ctxt = ExprContext { loop_num: *lbl, loop_handler: None, tail: tail_ctxt, ..ctxt };
             *lbl += 1;
             let loop_lbl = format!("loop_{}", new_ctxt.loop_num);
             instrs.push(Instr::Label(Val::Label(loop_lbl.clone())));
@@ -234,27 +443,87 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
         },
         // Break out of innermost loop with the following expression
         // moved into rax
-        Expr::Break(e) => {
-            if ctxt.loop_num > 0 {
-                let tail_ctxt = if ctxt.tail != TailContext::Invalid { TailContext::Valid } else { TailContext::Invalid };
-                instrs.append(&mut compile_expr(e, ExprContext { tail: tail_ctxt, ..ctxt }, lbl));
-                instrs.push(Instr::Jmp(Val::Label(format!("endloop_{}", ctxt.loop_num))));
-            } else {
-                panic!("Invalid break outside of loop");
+        RExprKind::Break(e) => {
+            let tail_ctxt = if ctxt.tail != TailContext::Invalid { TailContext::Valid } else { TailContext::Invalid };
+            instrs.append(&mut compile_expr(e, ExprContext { tail: tail_ctxt, ..ctxt }, lbl));
+            // Breaking out of a try body removes the handlers installed inside the loop
+            if let Some(si) = ctxt.loop_handler {
+                instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -si*WORD_SIZE)));
+                instrs.push(Instr::Mov(Val::LabelAddr(String::from("snek_handler")), Val::Reg(Reg::RBX)));
+            }
+            instrs.push(Instr::Jmp(Val::Label(format!("endloop_{}", ctxt.loop_num))));
+        },
+        // Raise the value in rax, with rsi holding the source location reported if nothing catches it
+        RExprKind::Raise(expr) => {
+            instrs.append(&mut compile_expr(expr, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RSI), Val::Imm(e.span.packed())));
+            instrs.push(Instr::Jmp(Val::Label(String::from("snek_raise"))));
+        },
+        // Assertions evaluate their message and panic when the condition is false, and are true otherwise
+        RExprKind::Assert(cond, msg, text) => {
+            if !ctxt.release {
+                let ok_lbl = format!("assert_ok_{}", lbl);
+                *lbl += 1;
+                instrs.append(&mut compile_expr(cond, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+                instrs.push(Instr::Cmp(Val::Reg(Reg::RAX), Val::Imm(FALSE_VAL)));
+                instrs.push(Instr::Jne(Val::Label(ok_lbl.clone())));
+                instrs.append(&mut compile_expr(msg, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+                instrs.append(&mut compile_panic(*text, &e.span, ctxt.si));
+                instrs.push(Instr::Label(Val::Label(ok_lbl)));
             }
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm(TRUE_VAL)));
+        },
+        RExprKind::Panic(msg, text) => {
+            instrs.append(&mut compile_expr(msg, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.append(&mut compile_panic(*text, &e.span, ctxt.si));
+        },
+        // Install a handler for the body, as a record on the stack holding the previous handler,
+        // the address of the catch code and the stack pointer to unwind to. Raising a value
+        // restores the stack pointer and the previous handler, then jumps to the catch code
+        // with the value in rax.
+        RExprKind::Try(body, id, handler) => {
+            let catch_lbl = format!("catch_{}", lbl);
+            let end_lbl = format!("endtry_{}", lbl);
+            *lbl += 1;
+
+            let record = Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE);
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::LabelAddr(String::from("snek_handler"))));
+            instrs.push(Instr::Mov(record.clone(), Val::Reg(Reg::RAX)));
+            instrs.push(Instr::Lea(Val::Reg(Reg::RAX), Val::LabelAddr(catch_lbl.clone())));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -(ctxt.si + 1)*WORD_SIZE), Val::Reg(Reg::RAX)));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -(ctxt.si + 2)*WORD_SIZE), Val::Reg(Reg::RSP)));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -(ctxt.si + 3)*WORD_SIZE), Val::Reg(Reg::RBP)));
+            instrs.push(Instr::Lea(Val::Reg(Reg::RAX), record.clone()));
+            instrs.push(Instr::Mov(Val::LabelAddr(String::from("snek_handler")), Val::Reg(Reg::RAX)));
+
+            // The body is not in tail position, since its handler is removed after it
+            let loop_handler = ctxt.loop_handler.or(Some(ctxt.si));
+            instrs.append(&mut compile_expr(body, ExprContext { si: ctxt.si + 4, loop_handler, tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), record.clone()));
+            instrs.push(Instr::Mov(Val::LabelAddr(String::from("snek_handler")), Val::Reg(Reg::RBX)));
+            instrs.push(Instr::Jmp(Val::Label(end_lbl.clone())));
+
+            // The caught value is bound in place of the record
+            instrs.push(Instr::Label(Val::Label(catch_lbl)));
+            let loc = LocPtr::LStack(-ctxt.si*WORD_SIZE);
+            instrs.push(Instr::Mov(loc.value(), Val::Reg(Reg::RAX)));
+            let new_env = ctxt.env.update(*id, loc);
+            instrs.append(&mut compile_expr(handler, ExprContext { si: ctxt.si + 1, env: &new_env, ..ctxt }, lbl));
+            instrs.push(Instr::Label(Val::Label(end_lbl)));
         },
         // Set a let-binding identifier to the result of the expression, by
         // moving rax into the corresponding stack address
-        Expr::Set(s, e) => {
-            if let Some(loc) = ctxt.env.get(s) {
-                instrs.append(&mut compile_expr(e, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
-                instrs.push(Instr::Mov(loc.value(), Val::Reg(Reg::RAX)));
-            } else {
-                panic!("Unbound variable identifier {}", s);
-            }
+        RExprKind::Set(id, e) => {
+            instrs.append(&mut compile_expr(e, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(ctxt.env[id].value(), Val::Reg(Reg::RAX)));
+        },
+        // Set the global in the data section to a new value, which is also the value of the expression
+        RExprKind::SetGlobal(gid, e) => {
+            instrs.append(&mut compile_expr(e, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::LabelAddr(format!("snek_global_{gid}")), Val::Reg(Reg::RAX)));
         },
         // Initialize a tuple with a specific size and fill with default value
-        Expr::TInit(e_length, e_value) => {
+        RExprKind::TInit(e_length, e_value) => {
             // Initialize labels
             let loop_lbl = format!("tinit_loop_{}", lbl);
             *lbl += 1;
@@ -267,13 +536,13 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
             instrs.append(&mut compile_expr(e_length, ExprContext { si: ctxt.si + 1, tail: TailContext::Invalid, ..ctxt }, lbl));
 
             // Type check length
-            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, lbl));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, &e.span, lbl));
 
             // Divide length by 2 to remove the value representation
             instrs.push(Instr::Sar(Val::Reg(Reg::RAX), Val::Imm(1)));
 
             // Bounds check length (>= 0)
-            instrs.append(&mut check_bnd(Val::Reg(Reg::RAX), Val::Imm(0), true, true));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RAX), Val::Imm(0), true, true, &e.span));
 
             // Allocate tuple on the heap
             instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE), Val::Reg(Reg::R15)));
@@ -294,18 +563,22 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
             instrs.push(Instr::Mov(Val::MemPtr(Reg::RBX, 0), Val::Reg(Reg::RCX)));
             instrs.push(Instr::Cmp(Val::Reg(Reg::RBX), Val::Reg(Reg::R15)));
             instrs.push(Instr::Jle(Val::Label(loop_lbl.clone())));
+
+            // Keep the heap pointer aligned for the next allocation
+            instrs.push(Instr::Add(Val::Reg(Reg::R15), Val::Imm((HEAP_ALIGN - 1).into())));
+            instrs.push(Instr::And(Val::Reg(Reg::R15), Val::Imm((-HEAP_ALIGN).into())));
             
             // Return tuple representation
             instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE)));
-            instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm(1)));
+            instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm(TUPLE_TAG)));
         }
         // Set a tuple's element at a certain index to a new expression value
-        Expr::TSet(e_tuple, e_index, e_value) => {
+        RExprKind::TSet(e_tuple, e_index, e_value) => {
             // Evaluate index
             instrs.append(&mut compile_expr(e_index, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
 
             // Perform type check for rax (index)
-            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, lbl));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, &e.span, lbl));
 
             // Convert index to internal number and store on stack
             instrs.push(Instr::Sar(Val::Reg(Reg::RAX), Val::Imm(1)));
@@ -320,15 +593,15 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
             instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::Reg(Reg::RAX)));
             
             // Perform type check for rax (tuple)
-            instrs.append(&mut check_msmx(Val::Reg(Reg::RBX), None, ValCheck::Tuple, lbl));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RBX), None, ValCheck::Tuple, &e.span, lbl));
 
             // Get heap pointer from tuple representation
-            instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(1)));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(TUPLE_TAG)));
 
             // perform out-of-bounds check (0<= i < len)
             instrs.push(Instr::Mov(Val::Reg(Reg::RCX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
-            instrs.append(&mut check_bnd(Val::Reg(Reg::RCX), Val::Imm(0), true, true));
-            instrs.append(&mut check_bnd(Val::Reg(Reg::RCX), Val::MemPtr(Reg::RAX, 0), false, false));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RCX), Val::Imm(0), true, true, &e.span));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RCX), Val::MemPtr(Reg::RAX, 0), false, false, &e.span));
 
             // shift memory pointer
             instrs.push(Instr::Mov(Val::Reg(Reg::RCX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
@@ -344,12 +617,12 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
             instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Reg(Reg::RBX)));
         },
         // Get a tuple's element at a certain index
-        Expr::TGet(e_tuple, e_index) => {
+        RExprKind::TGet(e_tuple, e_index) => {
             // Evaluate index
             instrs.append(&mut compile_expr(e_index, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
 
             // Perform type check for rax (index)
-            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, lbl));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, &e.span, lbl));
 
             // Convert index to correct iterator and store on stack
             instrs.push(Instr::Sar(Val::Reg(Reg::RAX), Val::Imm(1)));
@@ -359,15 +632,15 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
             instrs.append(&mut compile_expr(e_tuple, ExprContext { si: ctxt.si + 1, tail: TailContext::Invalid, ..ctxt }, lbl));
 
             // Perform type check for rax (tuple)
-            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Tuple, lbl));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Tuple, &e.span, lbl));
 
             // Get heap pointer from tuple representation
-            instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(1)));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(TUPLE_TAG)));
 
             // perform out-of-bounds check (0 <= i < len)
             instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
-            instrs.append(&mut check_bnd(Val::Reg(Reg::RBX), Val::Imm(0), true, true));
-            instrs.append(&mut check_bnd(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RAX, 0), false, false));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RBX), Val::Imm(0), true, true, &e.span));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RAX, 0), false, false, &e.span));
 
             // shift memory pointer
             instrs.push(Instr::Mov(Val::Reg(Reg::RCX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
@@ -378,9 +651,161 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
             // get value
             instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RAX, 0)));
         },
+        // Record value representation into rax, pointing to the descriptor of its struct followed by the fields
+        RExprKind::StructNew(sid, fields) => {
+            // Allocate record on the heap
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::Reg(Reg::R15)));
+            instrs.push(Instr::Add(Val::Reg(Reg::R15), Val::Imm(heap_size(fields.len() + 1).into())));
+            instrs.push(Instr::Lea(Val::Reg(Reg::RCX), Val::LabelAddr(format!("snek_struct_{sid}"))));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RBX, 0), Val::Reg(Reg::RCX)));
+
+            // Evaluate and place fields in record
+            for (offset, expr) in (1..).zip(fields) {
+                instrs.append(&mut compile_expr(expr, ExprContext { si: ctxt.si + 1, tail: TailContext::Invalid, ..ctxt }, lbl));
+                instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
+                instrs.push(Instr::Mov(Val::MemPtr(Reg::RBX, offset*WORD_SIZE), Val::Reg(Reg::RAX)));
+            }
+
+            // Return record representation
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
+            instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm(RECORD_TAG)));
+        },
+        // Whether a value is a record of the given struct, without any runtime error
+        RExprKind::StructIs(sid, e_record) => {
+            instrs.append(&mut compile_expr(e_record, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            let false_lbl = Val::Label(format!("struct_not_{lbl}"));
+            let end_lbl = Val::Label(format!("struct_is_end_{lbl}"));
+            *lbl += 1;
+
+            // Compare the heap tag, then the descriptor
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::Reg(Reg::RAX)));
+            instrs.push(Instr::And(Val::Reg(Reg::RBX), Val::Imm(HEAP_TAG_MASK)));
+            instrs.push(Instr::Cmp(Val::Reg(Reg::RBX), Val::Imm(RECORD_TAG)));
+            instrs.push(Instr::Jne(false_lbl.clone()));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RAX, -RECORD_TAG as i32)));
+            instrs.push(Instr::Lea(Val::Reg(Reg::RCX), Val::LabelAddr(format!("snek_struct_{sid}"))));
+            instrs.push(Instr::Cmp(Val::Reg(Reg::RBX), Val::Reg(Reg::RCX)));
+            instrs.push(Instr::Jne(false_lbl.clone()));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm(TRUE_VAL)));
+            instrs.push(Instr::Jmp(end_lbl.clone()));
+            instrs.push(Instr::Label(false_lbl));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm(FALSE_VAL)));
+            instrs.push(Instr::Label(end_lbl));
+        },
+        // Get a record's field
+        RExprKind::StructGet(sid, field, e_record) => {
+            instrs.append(&mut compile_expr(e_record, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+
+            // Perform type check for rax (record) and for its struct
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Record, &e.span, lbl));
+            instrs.append(&mut check_struct(Reg::RAX, *sid, &e.span));
+
+            // get value
+            let offset = (*field as i32 + 1)*WORD_SIZE - RECORD_TAG as i32;
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RAX, offset)));
+        },
+        // Set a record's field to a new expression value
+        RExprKind::StructSet(sid, field, e_record, e_value) => {
+            // Evaluate record and store on stack, then value into rax
+            instrs.append(&mut compile_expr(e_record, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::Reg(Reg::RAX)));
+            instrs.append(&mut compile_expr(e_value, ExprContext { si: ctxt.si + 1, tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
+
+            // Perform type check for rbx (record) and for its struct
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RBX), None, ValCheck::Record, &e.span, lbl));
+            instrs.append(&mut check_struct(Reg::RBX, *sid, &e.span));
+
+            // update value and return the record
+            let offset = (*field as i32 + 1)*WORD_SIZE - RECORD_TAG as i32;
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RBX, offset), Val::Reg(Reg::RAX)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Reg(Reg::RBX)));
+        },
+        // Try each case in order, evaluating the body of the first whose pattern matches
+        RExprKind::Match(expr, cases) => {
+            let match_num = *lbl;
+            *lbl += 1;
+            let end_lbl = format!("endmatch_{match_num}");
+
+            // Evaluate the value to match and store on stack
+            instrs.append(&mut compile_expr(expr, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::Reg(Reg::RAX)));
+
+            for (case_num, (pattern, body)) in cases.iter().enumerate() {
+                let next_lbl = format!("match_{match_num}_{}", case_num + 1);
+                let mut new_env = ctxt.env.clone();
+                let mut sii = ctxt.si + 1;
+                instrs.append(&mut compile_pattern(pattern, ctxt.si, &mut sii, &mut new_env, &next_lbl));
+                instrs.append(&mut compile_expr(body, ExprContext { si: sii, env: &new_env, ..ctxt }, lbl));
+                instrs.push(Instr::Jmp(Val::Label(end_lbl.clone())));
+                instrs.push(Instr::Label(Val::Label(next_lbl)));
+            }
+
+            // No case matched
+            instrs.append(&mut throw_error(MATCH_ERRCODE, &e.span));
+            instrs.push(Instr::Label(Val::Label(end_lbl)));
+        },
+        // Copy the bytes of a string between a start and an end index to a new string
+        RExprKind::Substring(e_string, e_start, e_end) => {
+            // Evaluate the string, start and end in order and store them on stack
+            instrs.append(&mut compile_expr(e_string, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::Reg(Reg::RAX)));
+            instrs.append(&mut compile_expr(e_start, ExprContext { si: ctxt.si + 1, tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE), Val::Reg(Reg::RAX)));
+            instrs.append(&mut compile_expr(e_end, ExprContext { si: ctxt.si + 2, tail: TailContext::Invalid, ..ctxt }, lbl));
+
+            // Perform type checks for the string, start and end (in rax)
+            instrs.append(&mut check_msmx(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), None, ValCheck::String, &e.span, lbl));
+            instrs.append(&mut check_msmx(Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE), None, ValCheck::Integer, &e.span, lbl));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, &e.span, lbl));
+
+            // perform out-of-bounds checks (0 <= start <= end <= len) on the tagged numbers
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE)));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RBX), Val::Imm(0), true, true, &e.span));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RAX), Val::Reg(Reg::RBX), true, true, &e.span));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RBX), Val::Imm(STRING_TAG)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RBX, 0)));
+            instrs.push(Instr::Add(Val::Reg(Reg::RBX), Val::Reg(Reg::RBX)));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RAX), Val::Reg(Reg::RBX), false, true, &e.span));
+
+            let args = [Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE),
+                        Val::Reg(Reg::RAX), Val::Reg(Reg::R15)];
+            instrs.append(&mut compile_runtime_call("snek_substring", &args, ctxt.si + 3));
+            instrs.append(&mut bump_string_heap());
+        },
+        RExprKind::TSlice(e_tuple, e_start, e_end) => {
+            // Evaluate the tuple, start and end in order and store them on stack
+            instrs.append(&mut compile_expr(e_tuple, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::Reg(Reg::RAX)));
+            instrs.append(&mut compile_expr(e_start, ExprContext { si: ctxt.si + 1, tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE), Val::Reg(Reg::RAX)));
+            instrs.append(&mut compile_expr(e_end, ExprContext { si: ctxt.si + 2, tail: TailContext::Invalid, ..ctxt }, lbl));
+
+            // Perform type checks for the tuple, start and end (in rax)
+            instrs.append(&mut check_msmx(Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), None, ValCheck::Tuple, &e.span, lbl));
+            instrs.append(&mut check_msmx(Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE), None, ValCheck::Integer, &e.span, lbl));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Integer, &e.span, lbl));
+
+            // perform out-of-bounds checks (0 <= start <= end <= len) on the tagged numbers
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE)));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RBX), Val::Imm(0), true, true, &e.span));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RAX), Val::Reg(Reg::RBX), true, true, &e.span));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE)));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RBX), Val::Imm(TUPLE_TAG)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RBX, 0)));
+            instrs.push(Instr::Add(Val::Reg(Reg::RBX), Val::Reg(Reg::RBX)));
+            instrs.append(&mut check_bnd(Val::Reg(Reg::RAX), Val::Reg(Reg::RBX), false, true, &e.span));
+
+            let args = [Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE), Val::MemPtr(Reg::RSP, -(ctxt.si+1)*WORD_SIZE),
+                        Val::Reg(Reg::RAX), Val::Reg(Reg::R15)];
+            instrs.append(&mut compile_runtime_call("snek_tuple_slice", &args, ctxt.si + 3));
+            instrs.append(&mut bump_tuple_heap());
+        },
         // A block of expressions each evaluated on its own, with the
         // value of the last expression moved to rax
-        Expr::Block(exprs) => {
+        RExprKind::Block(exprs) => {
             for i in 0..exprs.len() {
                 if i != exprs.len() - 1 {
                     instrs.append(&mut compile_expr(&exprs[i], ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
@@ -391,14 +816,8 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
         },
         // A call to an internal Snek function, which requires
         // a mis-aligned stack before the call
-        Expr::Call(fname, exprs) => {
-            if let Some(n) = ctxt.func_map.get(fname) {
-                if *n != exprs.len() as i32 {
-                    panic!("Incorrect number of function parameters for \'{}\'", fname);
-                }
-            } else {
-                panic!("Unknown function \'{}\'", fname);
-            }
+        RExprKind::Call(fid, exprs) => {
+            let fname = &ctxt.funcs[*fid].name;
             let offset = if (exprs.len() as i32+ctxt.si) % 2 == 0 { 0 } else { 1 };
             let mut sii     = ctxt.si+offset;
             for e in exprs.iter().rev() {
@@ -423,61 +842,548 @@ fn compile_expr(e: &Expr, ctxt : ExprContext, lbl: &mut i32) -> Vec<Instr> {
                 instrs.push(Instr::Call(Val::Label(fname.clone())));
                 instrs.push(Instr::Add(Val::Reg(Reg::RSP), Val::Imm((sii*WORD_SIZE) as i64)));
             }
+        },
+        // The callee evaluates the default value of an optional argument left out of a call
+        RExprKind::MissingArg => { instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm(MISSING_ARG))); },
+        // A lambda creates a closure holding the values of the variables it captures
+        RExprKind::Lambda(lid) => {
+            let lambda = &ctxt.lambdas[*lid];
+            let captures : Vec<Val> = lambda.captures.iter().map(|id| ctxt.env[id].value()).collect();
+            instrs.append(&mut compile_lambda_closure(*lid, lambda, &captures));
+        },
+        // A function definition used as a value is a closure without captures
+        RExprKind::FuncRef(fid) => {
+            let func = &ctxt.funcs[*fid];
+            let code = closure_code(&func.name, func.defaults.len(), func.variadic);
+            instrs.append(&mut compile_closure(&code, closure_arity(func.params.len(), func.defaults.len(), func.variadic), &[]));
+        },
+        // A call to a closure, which is passed to the function as an extra last
+        // argument so that its body can read the captured values. The number of
+        // arguments is passed in rcx for the closures of functions with optional or rest parameters,
+        // and the call leaves room for all of the parameters of the closure.
+        RExprKind::CallIndirect(callee, exprs) => {
+            let nargs = exprs.len() as i32 + 1;
+            let offset = if (nargs+ctxt.si) % 2 == 0 { 0 } else { 1 };
+            let mut sii     = ctxt.si+offset;
+            for e in std::iter::once(callee.as_ref()).chain(exprs.iter().rev()) {
+                let loc : LocPtr = LocPtr::LStack(-sii*WORD_SIZE);
+                instrs.append(&mut compile_expr(e, ExprContext { si: sii, tail: TailContext::Invalid, ..ctxt }, lbl));
+                instrs.push(Instr::Mov(loc.value(), Val::Reg(Reg::RAX)));
+                sii += 1;
+            }
+            sii -= 1;
+
+            // Type check the closure and its arity, get its number of parameters into rax, then
+            // its code pointer into rbx
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, -(ctxt.si+offset)*WORD_SIZE)));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RBX), None, ValCheck::Closure, &e.span, lbl));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RBX), Val::Imm(CLOSURE_TAG)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RCX), Val::Imm(exprs.len() as i64)));
+            instrs.append(&mut check_arity(&e.span));
+            instrs.append(&mut closure_nparams());
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RBX, WORD_SIZE)));
+
+            let call_lbl = format!("call_{}", lbl);
+            let wide_lbl = format!("call_wide_{}", lbl);
+            let done_lbl = format!("call_done_{}", lbl);
+            *lbl += 1;
+
+            // A tail call reuses the slots of the parameters of the function, if they leave room
+            // for all of the parameters of the closure
+            if ctxt.tail == TailContext::Valid && ctxt.in_func && exprs.len() < ctxt.farity {
+                let mut arg_i : i32 = nargs;
+                let diff : i32 = arg_i+ctxt.si+offset;    // arg_i here = nargs
+
+                instrs.push(Instr::Cmp(Val::Reg(Reg::RAX), Val::Imm(ctxt.farity as i64 - 1)));
+                instrs.push(Instr::Jg(Val::Label(call_lbl.clone())));
+                while arg_i > 0 {
+                    instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, (arg_i-diff)*WORD_SIZE)));
+                    instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, arg_i*WORD_SIZE), Val::Reg(Reg::RAX)));
+                    arg_i -= 1;
+                }
+                instrs.push(Instr::Jmp(Val::Reg(Reg::RBX)));
+            }
+            instrs.push(Instr::Label(Val::Label(call_lbl)));
+            instrs.push(Instr::Cmp(Val::Reg(Reg::RAX), Val::Imm(exprs.len() as i64)));
+            instrs.push(Instr::Jg(Val::Label(wide_lbl.clone())));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RSP), Val::Imm((sii*WORD_SIZE) as i64)));
+            instrs.push(Instr::Call(Val::Reg(Reg::RBX)));
+            instrs.push(Instr::Add(Val::Reg(Reg::RSP), Val::Imm((sii*WORD_SIZE) as i64)));
+            instrs.push(Instr::Jmp(Val::Label(done_lbl.clone())));
+
+            // With optional arguments left out, the stack pointer is saved in rbp while the
+            // arguments and the closure are copied below the slots in use, leaving room for all of
+            // the parameters
+            instrs.push(Instr::Label(Val::Label(wide_lbl)));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -(sii + 1)*WORD_SIZE), Val::Reg(Reg::RBP)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBP), Val::Reg(Reg::RSP)));
+            instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm((sii + 2).into())));
+            instrs.push(Instr::Shl(Val::Reg(Reg::RAX), Val::Imm(3)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RSI), Val::Reg(Reg::RSP)));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RSI), Val::Reg(Reg::RAX)));
+            instrs.push(Instr::And(Val::Reg(Reg::RSI), Val::Imm((-HEAP_ALIGN).into())));
+            for arg_i in 0..nargs {
+                instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, (arg_i-sii)*WORD_SIZE)));
+                instrs.push(Instr::Mov(Val::MemPtr(Reg::RSI, arg_i*WORD_SIZE), Val::Reg(Reg::RAX)));
+            }
+            instrs.push(Instr::Mov(Val::Reg(Reg::RSP), Val::Reg(Reg::RSI)));
+            instrs.push(Instr::Call(Val::Reg(Reg::RBX)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RSP), Val::Reg(Reg::RBP)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBP), Val::MemPtr(Reg::RSP, -(sii + 1)*WORD_SIZE)));
+            instrs.push(Instr::Label(Val::Label(done_lbl)));
+        },
+        // A call to a closure with the elements of a tuple as its arguments. Since the number of
+        // arguments is only known at runtime, the stack pointer is saved in rbp while the
+        // arguments are copied below the stack slots in use.
+        RExprKind::ApplyTuple(e_func, e_tuple) => {
+            let copy_lbl = format!("apply_copy_{}", lbl);
+            let done_lbl = format!("apply_done_{}", lbl);
+            *lbl += 1;
+            let func_loc = Val::MemPtr(Reg::RSP, -ctxt.si*WORD_SIZE);
+            let tuple_loc = Val::MemPtr(Reg::RSP, -(ctxt.si + 1)*WORD_SIZE);
+
+            instrs.append(&mut compile_expr(e_func, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(func_loc.clone(), Val::Reg(Reg::RAX)));
+            instrs.append(&mut compile_expr(e_tuple, ExprContext { si: ctxt.si + 1, tail: TailContext::Invalid, ..ctxt }, lbl));
+            instrs.push(Instr::Mov(tuple_loc.clone(), Val::Reg(Reg::RAX)));
+
+            // Type check the tuple and the closure, then check the arity against the tuple length
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RAX), None, ValCheck::Tuple, &e_tuple.span, lbl));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), func_loc.clone()));
+            instrs.append(&mut check_msmx(Val::Reg(Reg::RBX), None, ValCheck::Closure, &e_func.span, lbl));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RBX), Val::Imm(CLOSURE_TAG)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RCX), tuple_loc.clone()));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RCX), Val::Imm(TUPLE_TAG)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RCX), Val::MemPtr(Reg::RCX, 0)));
+            instrs.append(&mut check_arity(&e.span));
+            instrs.append(&mut closure_nparams());
+
+            // The new stack pointer in rdx leaves room for the elements or all of the parameters
+            // of the closure, whichever is more, and the closure, aligned
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -(ctxt.si + 2)*WORD_SIZE), Val::Reg(Reg::RBP)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBP), Val::Reg(Reg::RSP)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RSI), Val::Reg(Reg::RCX)));
+            instrs.push(Instr::Cmp(Val::Reg(Reg::RAX), Val::Reg(Reg::RSI)));
+            instrs.push(Instr::CMovg(Val::Reg(Reg::RSI), Val::Reg(Reg::RAX)));
+            instrs.push(Instr::Add(Val::Reg(Reg::RSI), Val::Imm((ctxt.si + 4).into())));
+            instrs.push(Instr::Shl(Val::Reg(Reg::RSI), Val::Imm(3)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Reg(Reg::RSP)));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RDX), Val::Reg(Reg::RSI)));
+            instrs.push(Instr::And(Val::Reg(Reg::RDX), Val::Imm((-HEAP_ALIGN).into())));
+
+            // Store the closure after the last argument, then copy the elements from the last
+            instrs.push(Instr::Mov(Val::Reg(Reg::RSI), Val::Reg(Reg::RCX)));
+            instrs.push(Instr::Shl(Val::Reg(Reg::RSI), Val::Imm(3)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), tuple_loc.clone()));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(TUPLE_TAG)));
+            instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Reg(Reg::RSI)));
+            instrs.push(Instr::Add(Val::Reg(Reg::RSI), Val::Reg(Reg::RDX)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), func_loc.clone()));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSI, 0), Val::Reg(Reg::RBX)));
+            instrs.push(Instr::Label(Val::Label(copy_lbl.clone())));
+            instrs.push(Instr::Cmp(Val::Reg(Reg::RSI), Val::Reg(Reg::RDX)));
+            instrs.push(Instr::Je(Val::Label(done_lbl.clone())));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RSI), Val::Imm(WORD_SIZE.into())));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RAX, 0)));
+            instrs.push(Instr::Mov(Val::MemPtr(Reg::RSI, 0), Val::Reg(Reg::RBX)));
+            instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Imm(WORD_SIZE.into())));
+            instrs.push(Instr::Jmp(Val::Label(copy_lbl)));
+            instrs.push(Instr::Label(Val::Label(done_lbl)));
+
+            // Reload the number of arguments and the code pointer, then call with the new stack pointer
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), func_loc));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RBX, WORD_SIZE - CLOSURE_TAG as i32)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RCX), tuple_loc));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RCX), Val::MemPtr(Reg::RCX, -TUPLE_TAG as i32)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RSP), Val::Reg(Reg::RDX)));
+            instrs.push(Instr::Call(Val::Reg(Reg::RBX)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RSP), Val::Reg(Reg::RBP)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBP), Val::MemPtr(Reg::RSP, -(ctxt.si + 2)*WORD_SIZE)));
+        },
+        // Programs with static errors are never compiled
+        RExprKind::Error => unreachable!("malformed expression reached code generation"),
+    }
+    instrs
+}
+
+/// Tests the value in stack slot loc against a pattern, jumping to fail_lbl if it does not match.
+/// Variables are bound to the slot holding their part of the value, and the tuple elements to
+/// test are copied to the slots from sii upward.
+fn compile_pattern(pattern: &RPattern, loc: i32, sii: &mut i32, env: &mut HashMap<BindId, LocPtr>, fail_lbl: &str) -> Vec<Instr> {
+    let mut instrs : Vec<Instr> = Vec::new();
+    let fail = Val::Label(fail_lbl.to_string());
+    match pattern {
+        RPattern::Wildcard => {},
+        // Literals match the values with the same representation
+        RPattern::Number(n) => {
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm(n*2)));
+            instrs.push(Instr::Cmp(Val::MemPtr(Reg::RSP, -loc*WORD_SIZE), Val::Reg(Reg::RAX)));
+            instrs.push(Instr::Jne(fail));
+        },
+        RPattern::Boolean(b) => {
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Imm(if *b { TRUE_VAL } else { FALSE_VAL })));
+            instrs.push(Instr::Cmp(Val::MemPtr(Reg::RSP, -loc*WORD_SIZE), Val::Reg(Reg::RAX)));
+            instrs.push(Instr::Jne(fail));
+        },
+        RPattern::Var(id) => { env.insert(*id, LocPtr::LStack(-loc*WORD_SIZE)); },
+        RPattern::Tuple(elems) => {
+            // Check for a tuple of the same length
+            instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -loc*WORD_SIZE)));
+            instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::Reg(Reg::RAX)));
+            instrs.push(Instr::And(Val::Reg(Reg::RBX), Val::Imm(HEAP_TAG_MASK)));
+            instrs.push(Instr::Cmp(Val::Reg(Reg::RBX), Val::Imm(TUPLE_TAG)));
+            instrs.push(Instr::Jne(fail.clone()));
+            instrs.push(Instr::Cmp(Val::MemPtr(Reg::RAX, -TUPLE_TAG as i32), Val::Imm(elems.len() as i64)));
+            instrs.push(Instr::Jne(fail));
+
+            // Test each element from its own stack slot
+            for (i, elem) in (1..).zip(elems) {
+                let elem_loc = *sii;
+                *sii += 1;
+                instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, -loc*WORD_SIZE)));
+                instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RAX, i*WORD_SIZE - TUPLE_TAG as i32)));
+                instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -elem_loc*WORD_SIZE), Val::Reg(Reg::RBX)));
+                instrs.append(&mut compile_pattern(elem, elem_loc, sii, env, fail_lbl));
+            }
+        },
+    }
+    instrs
+}
+
+/// Number of bytes a heap object of the given number of words takes, keeping the heap aligned
+fn heap_size(words: usize) -> i32 {
+    let size = words as i32 * WORD_SIZE;
+    (size + HEAP_ALIGN - 1) / HEAP_ALIGN * HEAP_ALIGN
+}
+
+/// Calls a runtime function with up to four arguments following the C calling convention,
+/// saving the input register in stack slot si during the call. The result is left in rax.
+fn compile_runtime_call(name: &str, args: &[Val], si: i32) -> Vec<Instr> {
+    let mut instrs : Vec<Instr> = Vec::new();
+    let offset = (si + if si % 2 == 0 { 1 } else { 0 })*WORD_SIZE;
+    instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -si*WORD_SIZE), Val::Reg(Reg::RDI)));
+    for (reg, arg) in [Reg::RDI, Reg::RSI, Reg::RDX, Reg::RCX].into_iter().zip(args) {
+        instrs.push(Instr::Mov(Val::Reg(reg), arg.clone()));
+    }
+    instrs.push(Instr::Sub(Val::Reg(Reg::RSP), Val::Imm(offset as i64)));
+    instrs.push(Instr::Call(Val::Label(name.to_string())));
+    instrs.push(Instr::Add(Val::Reg(Reg::RSP), Val::Imm(offset as i64)));
+    instrs.push(Instr::Mov(Val::Reg(Reg::RDI), Val::MemPtr(Reg::RSP, -si*WORD_SIZE)));
+    instrs
+}
+
+/// Stops the program with the message in rax, reporting the source text and location of the
+/// failed assertion or panic. Panics are not caught by try.
+fn compile_panic(text: StrId, span: &Span, si: i32) -> Vec<Instr> {
+    let mut instrs : Vec<Instr> = Vec::new();
+    instrs.push(Instr::Lea(Val::Reg(Reg::RBX), Val::LabelAddr(format!("snek_str_{text}"))));
+    instrs.push(Instr::Add(Val::Reg(Reg::RBX), Val::Imm(STRING_TAG)));
+    instrs.push(Instr::Lea(Val::Reg(Reg::RCX), Val::LabelAddr(String::from("snek_src_file"))));
+    let args = [Val::Reg(Reg::RAX), Val::Reg(Reg::RBX), Val::Imm(span.packed()), Val::Reg(Reg::RCX)];
+    instrs.append(&mut compile_runtime_call("snek_panic", &args, si));
+    instrs
+}
+
+/// Moves the heap pointer past the string the runtime just allocated there, whose
+/// tagged pointer is in rax
+fn bump_string_heap() -> Vec<Instr> {
+    vec![
+        Instr::Mov(Val::Reg(Reg::RBX), Val::Reg(Reg::RAX)),
+        Instr::Sub(Val::Reg(Reg::RBX), Val::Imm(STRING_TAG)),
+        Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RBX, 0)),
+        Instr::Add(Val::Reg(Reg::RBX), Val::Imm((WORD_SIZE + HEAP_ALIGN - 1).into())),
+        Instr::And(Val::Reg(Reg::RBX), Val::Imm((-HEAP_ALIGN).into())),
+        Instr::Add(Val::Reg(Reg::R15), Val::Reg(Reg::RBX)),
+    ]
+}
+
+/// Moves the heap pointer past the tuple the runtime just allocated there, whose
+/// tagged pointer is in rax
+fn bump_tuple_heap() -> Vec<Instr> {
+    vec![
+        Instr::Mov(Val::Reg(Reg::RBX), Val::Reg(Reg::RAX)),
+        Instr::Sub(Val::Reg(Reg::RBX), Val::Imm(TUPLE_TAG)),
+        Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RBX, 0)),
+        Instr::IMul(Val::Reg(Reg::RBX), Val::Imm(WORD_SIZE.into())),
+        Instr::Add(Val::Reg(Reg::RBX), Val::Imm((WORD_SIZE + HEAP_ALIGN - 1).into())),
+        Instr::And(Val::Reg(Reg::RBX), Val::Imm((-HEAP_ALIGN).into())),
+        Instr::Add(Val::Reg(Reg::R15), Val::Reg(Reg::RBX)),
+    ]
+}
+
+/// Lays out the string literals of a program in the data section, each as its length in bytes
+/// followed by its contents and aligned like heap objects
+fn compile_strings(strings: &[String]) -> String {
+    let mut data = String::new();
+    for (id, s) in strings.iter().enumerate() {
+        data += &format!("align {HEAP_ALIGN}\nsnek_str_{id}:\n  dq {}\n", s.len());
+        if !s.is_empty() {
+            data += &format!("  db {}\n", s.bytes().map(|b| b.to_string()).collect::<Vec<_>>().join(", "));
         }
     }
+    data
+}
+
+/// Lays out the descriptor of every struct in the data section, as its number of fields followed
+/// by its name and the names of its fields as C strings. Records point to their descriptor, which
+/// identifies their struct and lets the runtime print them.
+fn compile_structs(structs: &[RStruct]) -> String {
+    let mut data = String::new();
+    for (id, st) in structs.iter().enumerate() {
+        let names : Vec<&String> = std::iter::once(&st.name).chain(st.fields.iter()).collect();
+        let bytes : Vec<String> = names.iter().flat_map(|name| name.bytes().chain(std::iter::once(0))).map(|b| b.to_string()).collect();
+        data += &format!("align {WORD_SIZE}\nsnek_struct_{id}:\n  dq {}\n  db {}\n", st.fields.len(), bytes.join(", "));
+    }
+    data
+}
+
+/// Lays out the strings that handlers catch built-in runtime errors as, one per error code from
+/// MSMX_ERRCODE on, each padded to HEAP_ALIGN bytes so that the runtime can index them by code
+fn compile_error_codes() -> String {
+    let mut data = format!("align {HEAP_ALIGN}\nsnek_error_codes:\n");
+    for code in RUNTIME_ERROR_CODES {
+        let bytes : Vec<String> = code.bytes().map(|b| b.to_string()).collect();
+        data += &format!("  dq {}\n  db {}\n  align {HEAP_ALIGN}\n", code.len(), bytes.join(", "));
+    }
+    data
+}
+
+/// Lays out the input, the tuple of the arguments, the current handler and the globals of a program in the data section, each
+/// as one word that holds 0 until it is set (a handler of 0 means that no try is running), except
+/// for the globals which hold UNSET_GLOBAL
+fn compile_globals(globals: &[RExpr]) -> String {
+    let mut data = format!("align {WORD_SIZE}\nsnek_input:\n  dq 0\nsnek_args:\n  dq 0\nsnek_handler:\n  dq 0\n");
+    for id in 0..globals.len() {
+        data += &format!("snek_global_{id}:\n  dq {UNSET_GLOBAL}\n");
+    }
+    data
+}
+
+/// The arity stored in the closure of a function with the given number of parameters, of which
+/// the last ndefaults before the rest parameter are optional: the least number of arguments in the
+/// low 32 bits and how many more it accepts in the high 32 bits, with REST_ARITY added for a rest
+/// parameter
+fn closure_arity(nparams: usize, ndefaults: usize, variadic: bool) -> i64 {
+    let least = (nparams - ndefaults - usize::from(variadic)) as i64;
+    let more = ndefaults as i64 + if variadic { REST_ARITY } else { 0 };
+    least | more << 32
+}
+
+/// The label that closures of a function call, which is the code of the function itself unless
+/// it has optional or rest parameters
+fn closure_code(code: &str, ndefaults: usize, variadic: bool) -> String {
+    if ndefaults > 0 || variadic { format!("{code}_entry") } else { code.to_string() }
+}
+
+/// Allocates a closure on the heap, laid out as its arity, the address of its code and the
+/// captured values, and moves its tagged pointer into rax
+fn compile_closure(code: &str, arity: i64, captures: &[Val]) -> Vec<Instr> {
+    let mut instrs : Vec<Instr> = vec![
+        Instr::Mov(Val::Reg(Reg::RAX), Val::Imm(arity)),
+        Instr::Mov(Val::MemPtr(Reg::R15, 0), Val::Reg(Reg::RAX)),
+        Instr::Lea(Val::Reg(Reg::RAX), Val::LabelAddr(code.to_string())),
+        Instr::Mov(Val::MemPtr(Reg::R15, WORD_SIZE), Val::Reg(Reg::RAX)),
+    ];
+    for (offset, capture) in (2..).zip(captures) {
+        instrs.push(Instr::Mov(Val::Reg(Reg::RAX), capture.clone()));
+        instrs.push(Instr::Mov(Val::MemPtr(Reg::R15, offset*WORD_SIZE), Val::Reg(Reg::RAX)));
+    }
+    instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Reg(Reg::R15)));
+    instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm(CLOSURE_TAG)));
+    instrs.push(Instr::Add(Val::Reg(Reg::R15), Val::Imm(heap_size(captures.len() + 2).into())));
+    instrs
+}
+
+/// Allocates the closure of a lambda with the given captured values, whose code is the entry
+/// point of the lambda for its number of parameters
+fn compile_lambda_closure(id: LambdaId, lambda: &RLambda, captures: &[Val]) -> Vec<Instr> {
+    let code = closure_code(&format!("lambda_{id}"), lambda.defaults.len(), lambda.variadic);
+    compile_closure(&code, closure_arity(lambda.params.len(), lambda.defaults.len(), lambda.variadic), captures)
+}
+
+/// Compiles the entry point that closures of a function with optional or rest parameters use,
+/// which is passed the number of arguments in rcx. Calls to a closure leave room for all of its
+/// parameters, so the entry point packs the arguments after the others into a tuple on the heap
+/// for the rest parameter, moves the closure after the last parameter and passes the optional
+/// arguments left out as missing in place, then jumps to the code of the function.
+fn compile_entry(code: &str, nparams: usize, ndefaults: usize, variadic: bool, lbl: &mut i32) -> Vec<Instr> {
+    let mut instrs : Vec<Instr> = Vec::new();
+    let npositional = (nparams - usize::from(variadic)) as i32;
+    let nfixed = npositional - ndefaults as i32;
+
+    instrs.push(Instr::Label(Val::Label(format!("{code}_entry"))));
+
+    // The closure follows the arguments, at the address in rdx
+    instrs.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Reg(Reg::RCX)));
+    instrs.push(Instr::Add(Val::Reg(Reg::RDX), Val::Imm(1)));
+    instrs.push(Instr::Shl(Val::Reg(Reg::RDX), Val::Imm(3)));
+    instrs.push(Instr::Add(Val::Reg(Reg::RDX), Val::Reg(Reg::RSP)));
+
+    // Copy the extra arguments up to the closure after the tuple length, then move the heap
+    // pointer past the tuple and store it as the rest argument
+    if variadic {
+        let pack_lbl = format!("entry_pack_{}", lbl);
+        let done_lbl = format!("entry_done_{}", lbl);
+        *lbl += 1;
+        instrs.push(Instr::Lea(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RSP, (npositional+1)*WORD_SIZE)));
+        instrs.push(Instr::Mov(Val::Reg(Reg::RSI), Val::Reg(Reg::R15)));
+        instrs.push(Instr::Label(Val::Label(pack_lbl.clone())));
+        instrs.push(Instr::Cmp(Val::Reg(Reg::RBX), Val::Reg(Reg::RDX)));
+        instrs.push(Instr::Jge(Val::Label(done_lbl.clone())));
+        instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RBX, 0)));
+        instrs.push(Instr::Add(Val::Reg(Reg::RSI), Val::Imm(WORD_SIZE.into())));
+        instrs.push(Instr::Mov(Val::MemPtr(Reg::RSI, 0), Val::Reg(Reg::RAX)));
+        instrs.push(Instr::Add(Val::Reg(Reg::RBX), Val::Imm(WORD_SIZE.into())));
+        instrs.push(Instr::Jmp(Val::Label(pack_lbl)));
+        instrs.push(Instr::Label(Val::Label(done_lbl)));
+        instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Reg(Reg::RSI)));
+        instrs.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Reg(Reg::R15)));
+        instrs.push(Instr::Sar(Val::Reg(Reg::RAX), Val::Imm(3)));
+        instrs.push(Instr::Mov(Val::MemPtr(Reg::R15, 0), Val::Reg(Reg::RAX)));
+        instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Reg(Reg::R15)));
+        instrs.push(Instr::Add(Val::Reg(Reg::RAX), Val::Imm(TUPLE_TAG)));
+        instrs.push(Instr::Add(Val::Reg(Reg::RSI), Val::Imm((WORD_SIZE + HEAP_ALIGN - 1).into())));
+        instrs.push(Instr::And(Val::Reg(Reg::RSI), Val::Imm((-HEAP_ALIGN).into())));
+        instrs.push(Instr::Mov(Val::Reg(Reg::R15), Val::Reg(Reg::RSI)));
+    }
+
+    // Move the closure after the last parameter before the slot it was in can be overwritten
+    instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RDX, 0)));
+    instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, (nparams as i32 + 1)*WORD_SIZE), Val::Reg(Reg::RBX)));
+    if variadic {
+        instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, nparams as i32 * WORD_SIZE), Val::Reg(Reg::RAX)));
+    }
+    for arg_i in nfixed+1..=npositional {
+        let given_lbl = format!("entry_given_{}", lbl);
+        *lbl += 1;
+        instrs.push(Instr::Cmp(Val::Reg(Reg::RCX), Val::Imm(arg_i.into())));
+        instrs.push(Instr::Jge(Val::Label(given_lbl.clone())));
+        instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, arg_i*WORD_SIZE), Val::Imm(MISSING_ARG)));
+        instrs.push(Instr::Label(Val::Label(given_lbl)));
+    }
+    instrs.push(Instr::Jmp(Val::Label(code.to_string())));
+    instrs
+}
+
+/// Evaluates the default value of each optional parameter, the ones after the first nfixed
+/// parameters, whose argument was left out of the call
+fn compile_defaults(defaults: &[RExpr], nfixed: usize, ctxt: ExprContext, lbl: &mut i32) -> Vec<Instr> {
+    let mut instrs : Vec<Instr> = Vec::new();
+    for (sii, default) in (nfixed as i32 + 1..).zip(defaults) {
+        let given_lbl = format!("default_given_{}", lbl);
+        *lbl += 1;
+        let loc = Val::MemPtr(Reg::RSP, sii*WORD_SIZE);
+        instrs.push(Instr::Cmp(loc.clone(), Val::Imm(MISSING_ARG)));
+        instrs.push(Instr::Jne(Val::Label(given_lbl.clone())));
+        instrs.append(&mut compile_expr(default, ExprContext { tail: TailContext::Invalid, ..ctxt }, lbl));
+        instrs.push(Instr::Mov(loc, Val::Reg(Reg::RAX)));
+        instrs.push(Instr::Label(Val::Label(given_lbl)));
+    }
     instrs
 }
 
 /// Compile the function into a vector of instructions, including the header label and
 /// the ending ret instruction
-fn compile_func(func: &Function, func_map: &HashMap<String,i32>, lbl: &mut i32) -> Vec<Instr> {
+fn compile_func(func: &RFunction, funcs: &[RFunction], lambdas: &[RLambda], release: bool, lbl: &mut i32) -> Vec<Instr> {
     let mut instrs : Vec<Instr> = Vec::new();
-    let mut vars : HashMap<String, LocPtr> = HashMap::new();
+    let mut vars : HashMap<BindId, LocPtr> = HashMap::new();
 
     // Add each function parameter into the variable environment (scope)
-    let mut sii = 1;
-    for arg in &func.args {
-        vars.insert(arg.to_string(), LocPtr::LStack(sii*WORD_SIZE));
-        sii += 1;
+    for (sii, arg) in (1..).zip(func.params.iter()) {
+        vars.insert(*arg, LocPtr::LStack(sii*WORD_SIZE));
     }
 
     // Add the "label: " assembly label
     instrs.push(Instr::Label(Val::Label(func.name.clone())));
 
-    // Compile the inner expression
-    let ctxt = ExprContext { si: 1, env: &vars, loop_num: 0, func_map: &func_map, 
-                            in_func: true, tail: START_TAIL, farity: func.args.len() };
+    // Fill in the optional arguments left out, then compile the inner expression
+    let ctxt = ExprContext { si: 1, env: &vars, loop_num: 0, loop_handler: None, funcs, lambdas,
+                            in_func: true, tail: START_TAIL, farity: func.params.len(), release };
+    let nfixed = func.params.len() - func.defaults.len() - usize::from(func.variadic);
+    instrs.append(&mut compile_defaults(&func.defaults, nfixed, ctxt, lbl));
     instrs.append(&mut compile_expr(&func.body, ctxt, lbl));
 
     // ret instruction
     instrs.push(Instr::Ret);
+    if func.variadic || !func.defaults.is_empty() {
+        instrs.append(&mut compile_entry(&func.name, func.params.len(), func.defaults.len(), func.variadic, lbl));
+    }
     instrs
 }
 
-/// Compile a program into a String containing all functions represented
-/// in assembly instructions and a String containing the main expression represented
-/// in assembly instructions
-pub fn compile(prog: &Program) -> (String, String) {
-    let mut lbl = 1; // generator for unique label numbers
-    let mut func_map : HashMap<String,i32> = HashMap::new();    // represents list of function names
+/// Compile the body of a lambda into a vector of instructions, including the header label
+/// and the ending ret instruction. The closure is passed after the parameters, and the
+/// captured values are copied from it into the first stack slots of the body.
+fn compile_lambda(id: LambdaId, lambda: &RLambda, funcs: &[RFunction], lambdas: &[RLambda], release: bool,
+                  lbl: &mut i32) -> Vec<Instr> {
+    let mut instrs : Vec<Instr> = Vec::new();
+    let mut vars : HashMap<BindId, LocPtr> = HashMap::new();
+    let nparams = lambda.params.len() as i32;
+
+    // Add each parameter into the variable environment (scope)
+    for (sii, param) in (1..).zip(lambda.params.iter()) {
+        vars.insert(*param, LocPtr::LStack(sii*WORD_SIZE));
+    }
+
+    instrs.push(Instr::Label(Val::Label(format!("lambda_{id}"))));
 
-    // Insert all function names into the function map for future internal function calls
-    for func in &prog.defns {
-        func_map.insert(func.name.clone(),func.args.len() as i32);
+    // Copy each captured value out of the closure
+    if !lambda.captures.is_empty() {
+        instrs.push(Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RSP, (nparams+1)*WORD_SIZE)));
     }
+    for (sii, capture) in (1..).zip(lambda.captures.iter()) {
+        let field = (sii+1)*WORD_SIZE - CLOSURE_TAG as i32;
+        instrs.push(Instr::Mov(Val::Reg(Reg::RBX), Val::MemPtr(Reg::RAX, field)));
+        instrs.push(Instr::Mov(Val::MemPtr(Reg::RSP, -sii*WORD_SIZE), Val::Reg(Reg::RBX)));
+        vars.insert(*capture, LocPtr::LStack(-sii*WORD_SIZE));
+    }
+
+    // The closure argument counts towards the slots tail calls can reuse
+    let ctxt = ExprContext { si: lambda.captures.len() as i32 + 1, env: &vars, loop_num: 0, loop_handler: None, funcs, lambdas,
+                            in_func: true, tail: START_TAIL, farity: lambda.params.len() + 1, release };
+    let nfixed = lambda.params.len() - lambda.defaults.len() - usize::from(lambda.variadic);
+    instrs.append(&mut compile_defaults(&lambda.defaults, nfixed, ctxt, lbl));
+    instrs.append(&mut compile_expr(&lambda.body, ctxt, lbl));
+
+    instrs.push(Instr::Ret);
+    if lambda.variadic || !lambda.defaults.is_empty() {
+        instrs.append(&mut compile_entry(&format!("lambda_{id}"), lambda.params.len(), lambda.defaults.len(), lambda.variadic, lbl));
+    }
+    instrs
+}
+
+/// Compile a program into a String containing all functions represented
+/// in assembly instructions, a String containing the main expression represented
+/// in assembly instructions and a String containing its string literals, struct descriptors and globals as data.
+/// Assertions are left out of release builds.
+pub fn compile(prog: &RProgram, release: bool) -> (String, String, String) {
+    let mut lbl = 1; // generator for unique label numbers
 
     // Compile each function and append them together into a single instruction vector
     let mut defn_instrs = Vec::new();
-    for func in &prog.defns {
-        let mut c_instrs = compile_func(func, &func_map, &mut lbl);
+    for func in &prog.funcs {
+        let mut c_instrs = compile_func(func, &prog.funcs, &prog.lambdas, release, &mut lbl);
+        defn_instrs.append(&mut c_instrs);
+    }
+    for (id, lambda) in prog.lambdas.iter().enumerate() {
+        let mut c_instrs = compile_lambda(id, lambda, &prog.funcs, &prog.lambdas, release, &mut lbl);
         defn_instrs.append(&mut c_instrs);
     }
     
-    // Compile the main expression
-    let ctxt = ExprContext { si: 1, env: &HashMap::new(), loop_num: 0, func_map: &func_map, 
-                            in_func: false, tail: START_TAIL, farity: 0 };
-    let main_instrs = compile_expr(&prog.main, ctxt, &mut lbl);
+    // Store the input and the arguments, run the global initializers in order and compile the main expression
+    let ctxt = ExprContext { si: 1, env: &HashMap::new(), loop_num: 0, loop_handler: None, funcs: &prog.funcs, lambdas: &prog.lambdas,
+                            in_func: false, tail: START_TAIL, farity: 0, release };
+    let mut main_instrs = vec![
+        Instr::Mov(Val::LabelAddr(String::from("snek_input")), Val::Reg(Reg::RDI)),
+        Instr::Mov(Val::LabelAddr(String::from("snek_args")), Val::Reg(Reg::RDX)),
+    ];
+    for (id, global) in prog.globals.iter().enumerate() {
+        main_instrs.append(&mut compile_expr(global, ctxt, &mut lbl));
+        main_instrs.push(Instr::Mov(Val::LabelAddr(format!("snek_global_{id}")), Val::Reg(Reg::RAX)));
+    }
+    main_instrs.append(&mut compile_expr(&prog.main, ctxt, &mut lbl));
     
     // Convert each vector of instructions into Strings and return the tuple
-    (to_asm(&defn_instrs), to_asm(&main_instrs))
-}
\ No newline at end of file
+    let data = compile_strings(&prog.strings) + &compile_structs(&prog.structs) + &compile_globals(&prog.globals) + &compile_error_codes();
+    (to_asm(&defn_instrs), to_asm(&main_instrs), data)
+}
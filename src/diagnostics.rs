@@ -0,0 +1,253 @@
+/*
+    diagnostics.rs
+
+    Reports compiler diagnostics (errors and warnings) to stderr, either as human-readable text
+    with annotated source snippets or as one JSON object per line for editors and CI tools.
+*/
+
+use crate::types::*;
+
+use std::io::IsTerminal;
+
+/// ANSI styles used when colored output is enabled
+const BOLD : &str = "\x1b[1m";
+const RED : &str = "\x1b[1;31m";
+const YELLOW : &str = "\x1b[1;33m";
+const BLUE : &str = "\x1b[1;34m";
+const RESET : &str = "\x1b[0m";
+
+/// Number of columns a tab is expanded to in source snippets
+const TAB_WIDTH : usize = 4;
+
+/// Source file being compiled, used to resolve spans into file positions
+pub struct SourceFile<'a> {
+    pub name : &'a str,
+    pub text : &'a str,
+}
+
+impl<'a> SourceFile<'a> {
+    /// 1-based line and column of a byte offset in the source text
+    fn line_col(&self, offset: usize) -> (usize, usize) {
+        let before = &self.text[..offset.min(self.text.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+
+    /// Byte range of the line containing a byte offset, without its line break
+    fn line_range(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let start = self.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.text[offset..].find('\n').map_or(self.text.len(), |i| offset + i);
+        (start, end)
+    }
+}
+
+/// Underlined part of a source line in a snippet
+struct Mark<'a> {
+    col : usize,                // 0-based display column of the first underlined character
+    width : usize,              // number of underlined columns, at least 1
+    primary : bool,             // whether this is the main span of the diagnostic
+    message : Option<&'a str>,  // label printed next to or below the underline
+}
+
+/// Source line of a snippet with its underlines
+struct SnippetLine<'a> {
+    line : usize,           // 1-based line number
+    start : usize,          // byte offset of the start of the line
+    end : usize,            // byte offset of the end of the line, before the line break
+    marks : Vec<Mark<'a>>,
+}
+
+/// Display width of source text, with tabs expanded
+fn text_width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+/// Wraps text in an ANSI style if colors are enabled
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color { format!("{style}{text}{RESET}") } else { text.to_string() }
+}
+
+/// Prints every diagnostic to stderr in the given format. In human format, lines summarizing
+/// the number of errors and warnings and pointing to --explain follow. Human output is colored
+/// when stderr is a terminal, unless the NO_COLOR environment variable is set.
+pub fn report(diags: &[Diagnostic], src: &SourceFile, format: ErrorFormat) {
+    let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    for diag in diags {
+        match format {
+            ErrorFormat::Human => eprintln!("{}", render_human(diag, src, color)),
+            ErrorFormat::Json => eprintln!("{}", render_json(diag, src)),
+        }
+    }
+
+    if format == ErrorFormat::Human {
+        let errors = diags.iter().filter(|diag| diag.severity == Severity::Error).count();
+        let warnings = diags.len() - errors;
+        let summary = match (errors, warnings) {
+            (0, 0) => None,
+            (0, _) => Some(format!("{warnings} warning(s) emitted")),
+            (_, 0) => Some(format!("aborting due to {errors} previous error(s)")),
+            _ => Some(format!("aborting due to {errors} previous error(s); {warnings} warning(s) emitted")),
+        };
+        if let Some(summary) = summary {
+            eprintln!("{}", paint(&summary, BOLD, color));
+        }
+
+        // Points to the long-form explanations of the reported codes
+        let mut codes : Vec<&str> = diags.iter().map(|diag| diag.code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        match &codes[..] {
+            [] => {},
+            [code] => eprintln!("for more information about this code, try `diamondback --explain {code}`"),
+            _ => eprintln!("for more information about these codes ({}), try `diamondback --explain <code>`", codes.join(", ")),
+        }
+    }
+}
+
+/// Renders a diagnostic rustc-style: the message and its location, then every source line
+/// involved with the primary span underlined by carets and labels by dashes, then help
+fn render_human(diag: &Diagnostic, src: &SourceFile, color: bool) -> String {
+    let sev_style = match diag.severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+    };
+    let mut out = format!("{}{}\n",
+        paint(&format!("{}[{}]", diag.severity, diag.code), sev_style, color),
+        paint(&format!(": {}", diag.message), BOLD, color));
+
+    // Underlines grouped by line; spans over several lines are underlined to the end of their first line
+    let mut lines : Vec<SnippetLine> = Vec::new();
+    let spans = std::iter::once((&diag.span, true, None))
+        .chain(diag.labels.iter().map(|label| (&label.span, false, Some(label.message.as_str()))));
+    for (span, primary, message) in spans {
+        let (start, end) = src.line_range(span.start);
+        let first = span.start.min(end);
+        let mark = Mark {
+            col: text_width(&src.text[start..first]),
+            width: text_width(&src.text[first..span.end.clamp(first, end)]).max(1),
+            primary,
+            message,
+        };
+        match lines.iter_mut().find(|snippet| snippet.start == start) {
+            Some(snippet) => snippet.marks.push(mark),
+            None => lines.push(SnippetLine { line: src.line_col(start).0, start, end, marks: vec![mark] }),
+        }
+    }
+    lines.sort_by_key(|snippet| snippet.line);
+
+    let pad = " ".repeat(lines.last().map_or(1, |snippet| snippet.line.to_string().len()));
+    let style = |mark: &Mark| if mark.primary { sev_style } else { BLUE };
+    let gutter = |num: &str| paint(&format!("{num:>width$} |", width = pad.len()), BLUE, color);
+    out += &format!("{pad}{} {}:{}:{}\n", paint("-->", BLUE, color), src.name, diag.span.line, diag.span.col);
+    out += &format!("{}\n", gutter(""));
+
+    let mut prev_line : Option<usize> = None;
+    for mut snippet in lines {
+        if prev_line.is_some_and(|prev| snippet.line > prev + 1) {
+            out += &format!("{}\n", paint("...", BLUE, color));
+        }
+        prev_line = Some(snippet.line);
+        let text = src.text[snippet.start..snippet.end].replace('\t', &" ".repeat(TAB_WIDTH));
+        out += &format!("{} {}\n", gutter(&snippet.line.to_string()), text.trim_end());
+
+        // Underline row, with labels drawn over the primary span they fall within and the
+        // message of the underline ending the row inline
+        snippet.marks.sort_by_key(|mark| mark.col);
+        let marks = &snippet.marks;
+        let end = marks.iter().map(|mark| mark.col + mark.width).max().unwrap_or(0);
+        let mut cells : Vec<Option<usize>> = vec![None; end];
+        let order = (0..marks.len()).filter(|&i| marks[i].primary).chain((0..marks.len()).filter(|&i| !marks[i].primary));
+        for i in order {
+            cells[marks[i].col..marks[i].col + marks[i].width].fill(Some(i));
+        }
+        let mut underline = String::new();
+        let mut col = 0;
+        while col < end {
+            let run = cells[col..].iter().take_while(|cell| **cell == cells[col]).count();
+            underline += &match cells[col] {
+                Some(i) => paint(&(if marks[i].primary { "^" } else { "-" }).repeat(run), style(&marks[i]), color),
+                None => " ".repeat(run),
+            };
+            col += run;
+        }
+        let inline = cells.last().copied().flatten().filter(|&i| marks[i].message.is_some());
+        if let Some(i) = inline {
+            underline += &format!(" {}", paint(marks[i].message.unwrap_or_default(), style(&marks[i]), color));
+        }
+        let mut hanging : Vec<&Mark> = (0..marks.len())
+            .filter(|&i| marks[i].message.is_some() && Some(i) != inline)
+            .map(|i| &marks[i])
+            .collect();
+        out += &format!("{} {}\n", gutter(""), underline);
+
+        // Other messages hang below their underline, connected by bars, rightmost first
+        while let Some(mark) = hanging.pop() {
+            let (bars, col) = connectors(&hanging, sev_style, color);
+            let bar = paint("|", style(mark), color);
+            out += &format!("{} {bars}{}{bar}\n", gutter(""), " ".repeat(mark.col.saturating_sub(col)));
+            let message = paint(mark.message.unwrap_or_default(), style(mark), color);
+            out += &format!("{} {bars}{}{message}\n", gutter(""), " ".repeat(mark.col.saturating_sub(col)));
+        }
+    }
+
+    if let Some(help) = &diag.help {
+        out += &format!("{}\n{pad} {} {help}\n", gutter(""), paint("= help:", BOLD, color));
+    }
+    out
+}
+
+/// Row of bars under the given underlines, with the display width of the row
+fn connectors(marks: &[&Mark], sev_style: &str, color: bool) -> (String, usize) {
+    let mut row = String::new();
+    let mut col = 0;
+    for mark in marks {
+        if mark.col >= col {
+            row += &" ".repeat(mark.col - col);
+            row += &paint("|", if mark.primary { sev_style } else { BLUE }, color);
+            col = mark.col + 1;
+        }
+    }
+    (row, col)
+}
+
+/// Renders a diagnostic as a single-line JSON object
+fn render_json(diag: &Diagnostic, src: &SourceFile) -> String {
+    let labels = diag.labels.iter()
+        .map(|label| format!("{{\"span\":{},\"message\":{}}}", json_span(&label.span, src), json_string(&label.message)))
+        .collect::<Vec<_>>()
+        .join(",");
+    let help = match &diag.help {
+        Some(help) => json_string(help),
+        None => String::from("null"),
+    };
+    format!("{{\"code\":{},\"name\":{},\"severity\":{},\"message\":{},\"span\":{},\"labels\":[{}],\"help\":{}}}",
+        json_string(diag.code), json_string(diag.name), json_string(&diag.severity.to_string()), json_string(&diag.message),
+        json_span(&diag.span, src), labels, help)
+}
+
+/// Renders a span as a JSON object with its byte offsets and start/end positions
+fn json_span(span: &Span, src: &SourceFile) -> String {
+    let (end_line, end_col) = src.line_col(span.end);
+    format!("{{\"file\":{},\"start\":{},\"end\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{}}}",
+        json_string(src.name), span.start, span.end, span.line, span.col, end_line, end_col)
+}
+
+/// Quotes and escapes a string as a JSON string literal
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,405 @@
+/*
+    explain.rs
+
+    Long-form descriptions of every static error (E), runtime error (R) and lint (W) code,
+    printed by `diamondback --explain <code>`.
+*/
+
+use crate::types::*;
+
+/// Every error and lint code with its explanation
+pub const EXPLANATIONS : [Explanation; 35] = [
+    Explanation {
+        code: "E0001",
+        name: "invalid-sexp",
+        summary: "the source is not a well-formed sequence of s-expressions",
+        description: "\
+A Snek program is a sequence of s-expressions. Every '(' must be closed by a matching ')',
+every block comment '#|' must be closed by '|#', and a datum comment '#;' must be followed
+by the s-expression it comments out. String literals must end with '\"' and only use the
+escapes \\n, \\t, \\r, \\0, \\\\ and \\\".",
+        failing: "(+ 1 (* 2 3)",
+        fixed: "(+ 1 (* 2 3))",
+    },
+    Explanation {
+        code: "E0002",
+        name: "empty-program",
+        summary: "the program has no definitions and no main expression",
+        description: "\
+A program must contain at least a main expression, whose value is printed when the program
+runs. A file holding only whitespace and comments is rejected.",
+        failing: "; TODO: compute the answer",
+        fixed: "; the answer\n42",
+    },
+    Explanation {
+        code: "E0003",
+        name: "missing-main",
+        summary: "the program does not end with a main expression",
+        description: "\
+Function definitions must be followed by exactly one main expression, which is evaluated
+when the program runs. A program that ends with a definition has nothing to evaluate.",
+        failing: "(fun (double x) (* x 2))",
+        fixed: "(fun (double x) (* x 2))\n(double 21)",
+    },
+    Explanation {
+        code: "E0004",
+        name: "invalid-definition",
+        summary: "a function definition is not of the form (fun (name params...) body)",
+        description: "\
+A function definition lists the function name and its parameters together in one
+parenthesized list, followed by a single body expression.",
+        failing: "(fun double (x) (* x 2))\n(double 21)",
+        fixed: "(fun (double x) (* x 2))\n(double 21)",
+    },
+    Explanation {
+        code: "E0005",
+        name: "invalid-form",
+        summary: "a keyword form has the wrong shape",
+        description: "\
+Keyword forms have a fixed shape: (let ((name expr)...) body), (if cond then else),
+(loop body), (break expr), (set! name expr), (lambda (param...) body), (block expr...)
+with at least one expression, (match expr (pattern body)...) with at least one case and
+(cond (test body)... (else body)) ending with an else clause. A struct is declared at the top level as (struct name (field...)).
+The empty list () is not an expression either.",
+        failing: "(if true 1)",
+        fixed: "(if true 1 0)",
+    },
+    Explanation {
+        code: "E0006",
+        name: "unknown-operator",
+        summary: "a list starts with something that is neither an operator nor a name",
+        description: "\
+The first element of a list expression must be a keyword, an operator, a name or a nested
+expression computing a function. Numbers and symbols that are not valid identifiers cannot be
+applied.",
+        failing: "(** 2 3)",
+        fixed: "(* 2 (* 2 2))",
+    },
+    Explanation {
+        code: "E0007",
+        name: "number-out-of-range",
+        summary: "an integer literal does not fit in a Snek integer",
+        description: "\
+Snek integers are 63-bit signed integers, between -4611686018427387904 and
+4611686018427387903. Integer literals outside of this range are rejected.",
+        failing: "4611686018427387904",
+        fixed: "4611686018427387903",
+    },
+    Explanation {
+        code: "E0008",
+        name: "invalid-identifier",
+        summary: "a name does not follow the identifier naming rules",
+        description: "\
+Variable, parameter and function names must start with a letter, followed only by letters
+and digits.",
+        failing: "(let ((my-value 1)) my-value)",
+        fixed: "(let ((myValue 1)) myValue)",
+    },
+    Explanation {
+        code: "E0009",
+        name: "keyword-misuse",
+        summary: "a keyword is used as a name",
+        description: "\
+Keywords and operator names such as let, if, loop, tuple and add1 are reserved, and cannot
+be used as variable, parameter or function names.",
+        failing: "(let ((loop 1)) loop)",
+        fixed: "(let ((count 1)) count)",
+    },
+    Explanation {
+        code: "E0010",
+        name: "duplicate-function",
+        summary: "two functions are defined with the same name",
+        description: "\
+Every function in a program must have a distinct name, so that each call refers to exactly
+one definition. This includes the operations generated by struct declarations: the
+constructor, name?, name-field and set-name-field!.",
+        failing: "(fun (area w h) (* w h))\n(fun (area s) (* s s))\n(area 2 3)",
+        fixed: "(fun (area w h) (* w h))\n(fun (square s) (* s s))\n(+ (area 2 3) (square 4))",
+    },
+    Explanation {
+        code: "E0011",
+        name: "duplicate-parameter",
+        summary: "a function has two parameters with the same name",
+        description: "\
+The parameters of a function must have distinct names, so that each use in the body refers
+to exactly one argument.",
+        failing: "(fun (add x x) (+ x x))\n(add 1 2)",
+        fixed: "(fun (add x y) (+ x y))\n(add 1 2)",
+    },
+    Explanation {
+        code: "E0012",
+        name: "duplicate-binding",
+        summary: "a let expression binds the same name twice",
+        description: "\
+The bindings of a single let must have distinct names. To replace the value of a name, use
+a nested let (which shadows the outer binding) or set!.",
+        failing: "(let ((x 1) (x 2)) x)",
+        fixed: "(let ((x 1) (y 2)) (+ x y))",
+    },
+    Explanation {
+        code: "E0013",
+        name: "unbound-identifier",
+        summary: "a variable is used or assigned outside of the scope of any binding",
+        description: "\
+A variable must be bound by an enclosing let, be a parameter of the enclosing function or
+lambda, or be a global. The bindings of a let are visible to the later bindings of the same let
+and to its body, and a global is visible everywhere except in the initializers of earlier globals.",
+        failing: "(let ((x 1)) (+ x y))",
+        fixed: "(let ((x 1) (y 2)) (+ x y))",
+    },
+    Explanation {
+        code: "E0014",
+        name: "unknown-function",
+        summary: "a call to a function that is not defined",
+        description: "\
+Every function that is called must be defined with fun somewhere in the program. Functions
+may be defined before or after the places they are called from.",
+        failing: "(fun (double x) (* x 2))\n(triple 3)",
+        fixed: "(fun (double x) (* x 2))\n(double 3)",
+    },
+    Explanation {
+        code: "E0015",
+        name: "wrong-arity",
+        summary: "a function or operator is given the wrong number of arguments",
+        description: "\
+A call must pass exactly as many arguments as the function has parameters, except that it may
+leave out optional parameters, written (name default), and pass any number of extra arguments
+to a rest parameter. Built-in operators take a fixed number of arguments: for example + takes
+two and add1 one.",
+        failing: "(fun (add x y) (+ x y))\n(add 1)",
+        fixed: "(fun (add x y) (+ x y))\n(add 1 2)",
+    },
+    Explanation {
+        code: "E0016",
+        name: "input-in-function",
+        summary: "'input' is used inside a function body (no longer reported)",
+        description: "\
+This error is no longer reported. The program input used to be available only in the main
+expression, so functions that needed it had to receive it as an argument. input is now a
+global, visible in every function, lambda and global initializer.",
+        failing: "(fun (inc) (+ input 1))\n(inc)",
+        fixed: "(fun (inc x) (+ x 1))\n(inc input)",
+    },
+    Explanation {
+        code: "E0017",
+        name: "break-outside-loop",
+        summary: "break is used outside of any loop",
+        description: "\
+break exits the innermost enclosing loop with a value, so it can only appear in the body of
+a loop.",
+        failing: "(let ((x 5)) (if (> x 3) (break x) 0))",
+        fixed: "(let ((x 0)) (loop (if (> x 3) (break x) (set! x (add1 x)))))",
+    },
+    Explanation {
+        code: "E0018",
+        name: "duplicate-field",
+        summary: "a struct declares the same field twice",
+        description: "\
+Every field of a struct must have a distinct name, so that each accessor and mutator refers to
+exactly one field.",
+        failing: "(struct point (x x))\n(point 1 2)",
+        fixed: "(struct point (x y))\n(point 1 2)",
+    },
+    Explanation {
+        code: "E0019",
+        name: "invalid-pattern",
+        summary: "a match case has a pattern that is not a literal, a name, _ or a tuple of patterns",
+        description: "\
+A pattern is a number or boolean literal, matching an equal value, a name, matching any value and
+binding it, _, matching any value without binding it, or (tuple pattern...), matching a tuple of
+exactly that length whose elements match the nested patterns. Other expressions are not patterns.",
+        failing: "(match (tuple 1 2) ((+ 1 2) 0) (_ 1))",
+        fixed: "(match (tuple 1 2) ((tuple 1 x) x) (_ 1))",
+    },
+    Explanation {
+        code: "E0020",
+        name: "duplicate-global",
+        summary: "a global is defined with the name of another global or of a function",
+        description: "\
+Globals defined with def share one namespace with functions and the operations generated by
+struct declarations, since all of them can be called by name from anywhere in the program.
+Every global must have a name distinct from the other globals and from those functions.",
+        failing: "(fun (total x) x)\n(def total 0)\n(total 1)",
+        fixed: "(fun (total x) x)\n(def sum 0)\n(total sum)",
+    },
+    Explanation {
+        code: "E0021",
+        name: "captured-assignment",
+        summary: "set! changes a variable that a lambda captures",
+        description: "\
+A lambda copies the values of the variables it captures when it is evaluated, so a set! on such
+a variable, inside the lambda or outside of it, would leave the copies out of date. A value that
+a lambda shares with the rest of the program can be kept in a tuple, a record or a global, which
+lambdas see the changes of.",
+        failing: "(let ((n 0) (incr (lambda () (set! n (add1 n))))) (block (incr) n))",
+        fixed: "(let ((n (tuple 0)) (incr (lambda () (tset n 0 (add1 (tget n 0)))))) (block (incr) (tget n 0)))",
+    },
+    Explanation {
+        code: "R0001",
+        name: "invalid-argument",
+        summary: "an operation was given a value of the wrong type at runtime",
+        description: "\
+Arithmetic, bitwise and comparison operators take numbers, and, or and not take booleans, tuple operations take a tuple and a
+number index, string operations take strings (and numbers for substring indices), and =
+compares two values of the same type. Struct accessors and mutators take a record. The program stops with this error
+when an operation receives a value of another type.",
+        failing: "(fun (inc x) (+ x 1))\n(inc true)",
+        fixed: "(fun (inc x) (+ x 1))\n(inc 1)",
+    },
+    Explanation {
+        code: "R0002",
+        name: "overflow",
+        summary: "an arithmetic operation overflowed at runtime",
+        description: "\
+Snek integers are 63-bit signed integers, between -4611686018427387904 and
+4611686018427387903. The program stops with this error when the result of an arithmetic
+operation does not fit in this range.",
+        failing: "(* 4611686018427387903 2)",
+        fixed: "(* 2305843009213693951 2)",
+    },
+    Explanation {
+        code: "R0003",
+        name: "index-out-of-bounds",
+        summary: "a tuple index or size is out of bounds at runtime",
+        description: "\
+Tuple indices start at 0 and must be smaller than the length of the tuple, and the size
+given to tinit cannot be negative. The start and end of a substring must satisfy
+0 <= start <= end <= (string-length s), and those of a tslice 0 <= start <= end <= (tlen t).
+The program stops with this error otherwise.",
+        failing: "(tget (tuple 1 2 3) 3)",
+        fixed: "(tget (tuple 1 2 3) 2)",
+    },
+    Explanation {
+        code: "R0004",
+        name: "arity-mismatch",
+        summary: "a function value was called with the wrong number of arguments at runtime",
+        description: "\
+Calls to a lambda or to a function stored in a variable are only checked when they run, since
+the function called is not known at compile time, as are the calls made by apply. The program
+stops with this error when the number of arguments differs from the number of parameters of the
+function, allowing for its optional and rest parameters.",
+        failing: "(let ((add (lambda (x y) (+ x y)))) (add 1))",
+        fixed: "(let ((add (lambda (x y) (+ x y)))) (add 1 2))",
+    },
+    Explanation {
+        code: "R0005",
+        name: "struct-mismatch",
+        summary: "a field operation was given a record of another struct at runtime",
+        description: "\
+The accessors and mutators generated by a struct declaration only work on records built by the
+constructor of that struct. The program stops with this error when one of them receives a record
+of another struct, even if it has a field of the same name. Use the name? predicate to tell records
+apart.",
+        failing: "(struct point (x y))\n(struct size (x y))\n(point-x (size 1 2))",
+        fixed: "(struct point (x y))\n(struct size (x y))\n(size-x (size 1 2))",
+    },
+    Explanation {
+        code: "R0006",
+        name: "match-failure",
+        summary: "no case of a match expression matched the value at runtime",
+        description: "\
+The cases of a match are tried in order, and the program stops with this error when none of their
+patterns matches the value. End the match with a _ case to handle every other value.",
+        failing: "(match (tuple 1 2) ((tuple x) x))",
+        fixed: "(match (tuple 1 2) ((tuple x) x) (_ 0))",
+    },
+    Explanation {
+        code: "R0007",
+        name: "division-by-zero",
+        summary: "an integer was divided by zero at runtime",
+        description: "\
+The divisor of /, quotient, remainder and modulo must not be zero. The program stops with this
+error when it is, so check the divisor first when it can be zero.",
+        failing: "(fun (average total count) (/ total count))\n(average 10 0)",
+        fixed: "(fun (average total count) (if (= count 0) 0 (/ total count)))\n(average 10 0)",
+    },
+    Explanation {
+        code: "R0008",
+        name: "uninitialized-global",
+        summary: "a global was read before its initializer had run",
+        description: "\
+The initializers of the globals run in the order of the definitions. An initializer can only
+name the globals defined before it, but a function it calls can read any global, and reading
+one whose initializer has not run yet stops the program with this error. Define the global
+before the one whose initializer needs it.",
+        failing: "(fun (total) (+ base 1))\n(def start (total))\n(def base 10)\nstart",
+        fixed: "(fun (total) (+ base 1))\n(def base 10)\n(def start (total))\nstart",
+    },
+    Explanation {
+        code: "R0009",
+        name: "uncaught-exception",
+        summary: "a raised value was not caught by any try",
+        description: "\
+(raise v) stops the evaluation and hands v to the handler of the innermost running try. When no
+try is running, the program stops with this error and prints the value. Built-in runtime errors
+are caught the same way, as the string of their code such as \"R0003\".",
+        failing: "(fun (check n) (if (< n 0) (raise \"negative\") n))\n(check -1)",
+        fixed: "(fun (check n) (if (< n 0) (raise \"negative\") n))\n(try (check -1) (catch e 0))",
+    },
+    Explanation {
+        code: "W0001",
+        name: "unused-binding",
+        summary: "a let binding is never read",
+        description: "\
+The value bound by a let is never used, which often means the wrong variable is used
+elsewhere. Assigning to the variable with set! does not count as using it. This lint can be
+set with -W/-A/-D unused-binding.",
+        failing: "(let ((x 1) (y 2)) x)",
+        fixed: "(let ((x 1)) x)",
+    },
+    Explanation {
+        code: "W0002",
+        name: "unused-parameter",
+        summary: "a function parameter is never read",
+        description: "\
+A function ignores one of its arguments. Remove the parameter, along with the matching
+argument at each call. This lint can be set with -W/-A/-D unused-parameter.",
+        failing: "(fun (first a b) a)\n(first 1 2)",
+        fixed: "(fun (first a) a)\n(first 1)",
+    },
+    Explanation {
+        code: "W0003",
+        name: "unused-function",
+        summary: "a function cannot be reached from the main expression",
+        description: "\
+The function is never called, either directly from the main expression or from any function
+that is. Functions that only call each other are unused too. This lint can be set with
+-W/-A/-D unused-function.",
+        failing: "(fun (double x) (* x 2))\n5",
+        fixed: "(fun (double x) (* x 2))\n(double 5)",
+    },
+    Explanation {
+        code: "W0004",
+        name: "shadowed-binding",
+        summary: "a let binding hides a parameter or an outer let binding",
+        description: "\
+Inside the body of the inner let, the outer variable of the same name cannot be read, which
+makes it easy to confuse the two. This lint can be set with -W/-A/-D shadowed-binding.",
+        failing: "(let ((x 1)) (+ x (let ((x 2)) x)))",
+        fixed: "(let ((x 1)) (+ x (let ((y 2)) y)))",
+    },
+    Explanation {
+        code: "W0005",
+        name: "definite-failure",
+        summary: "an operation always fails its runtime type check",
+        description: "\
+The argument of an operation is known at compile time to be of the wrong kind, for example a
+boolean passed to add1 or a number used as a tuple, so the operation would stop the program
+with an invalid-argument error (R0001) every time it runs. Kinds are followed through let, if
+and block; parameters, call results, tuple elements and variables changed by set! can hold any
+kind. An operation inside a function is only reported when the main expression or a global
+initializer always calls the function. This lint is denied by default; -W definite-failure
+reports it as a warning instead and compiles the program anyway.",
+        failing: "(let ((flag true)) (add1 flag))",
+        fixed: "(let ((count 1)) (add1 count))",
+    },
+];
+
+/// Renders the long-form description of a code (e.g. E0012) or code name
+/// (e.g. duplicate-binding), or None if there is no such code
+pub fn explain(code: &str) -> Option<String> {
+    let expl = EXPLANATIONS.iter().find(|expl| expl.code == code || expl.name == code)?;
+    let indent = |src: &str| src.lines().map(|line| format!("    {line}\n")).collect::<String>();
+    Some(format!("{} {}: {}\n\n{}\n\nErroneous example:\n\n{}\nFixed example:\n\n{}",
+        expl.code, expl.name, expl.summary, expl.description, indent(expl.failing), indent(expl.fixed)))
+}
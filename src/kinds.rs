@@ -0,0 +1,508 @@
+/*
+    kinds.rs
+
+    Definite-failure analysis over a resolved program: tracks the kind of value (number, boolean,
+    tuple, function, string or record) every expression is known to produce through lets, ifs and blocks, and reports operations
+    whose runtime type check is guaranteed to fail whenever the program runs.
+*/
+
+use crate::types::*;
+
+/// What is statically known about the value of an expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Known {
+    Kind(ValueKind),    // always a value of this kind
+    Any,                // possibly a value of any kind
+    Fails,              // always stops the program with a failed type check
+    Exits,              // never produces a value, since it breaks out of a loop, raises or fails
+}
+
+impl Known {
+    /// Whether evaluation continues with a value
+    fn is_value(self) -> bool {
+        matches!(self, Known::Kind(_) | Known::Any)
+    }
+
+    /// What is known about a value produced by either of two paths
+    fn join(self, other: Known) -> Known {
+        match (self, other) {
+            (k1, k2) if k1 == k2 => k1,
+            (known, k) | (k, known) if !k.is_value() && known.is_value() => known,
+            (Known::Fails | Known::Exits, Known::Fails | Known::Exits) => Known::Exits,
+            _ => Known::Any,
+        }
+    }
+
+    /// Result of an expression after its operands are evaluated in order: the first operand
+    /// that does not produce a value stops the evaluation
+    fn after(operands: &[Known], result: Known) -> Known {
+        operands.iter().copied().find(|known| !known.is_value()).unwrap_or(result)
+    }
+}
+
+/// State of the analysis, shared across the whole program
+struct KindChecker<'a> {
+    structs : &'a [RStruct],        // struct declarations, naming the operations they generate
+    kinds : Vec<Known>,             // known value of every binding, indexed by BindId
+    assigned : Vec<bool>,           // whether each binding is the target of a set!
+    failures : Vec<Warning>,        // failing operations that every path of the current expression reaches
+    funcs : Vec<Option<Vec<Warning>>>,  // failing operations of every function whose body always fails, indexed by FuncId
+    release : bool,                 // whether assertions are left out of the compiled program
+}
+
+/// Checks the global initializers and the main expression, returning an error for every failing
+/// operation when one of them fails on every execution path. A call to a function whose body always
+/// fails also fails, so failures inside functions are only reported when such a call always runs.
+/// Parameters, function results, tuple elements, globals and variables changed by set! can hold any kind of value.
+/// Assertions are not checked in release builds, which do not evaluate them.
+pub fn check_kinds(prog: &RProgram, release: bool) -> Vec<Warning> {
+    let mut checker = KindChecker {
+        structs: &prog.structs,
+        kinds: vec![Known::Any; prog.bindings.len()],
+        assigned: vec![false; prog.bindings.len()],
+        failures: Vec::new(),
+        funcs: vec![None; prog.funcs.len()],
+        release,
+    };
+    let funcs = prog.funcs.iter().flat_map(|func| func.defaults.iter().chain(std::iter::once(&func.body)));
+    let lambdas = prog.lambdas.iter().flat_map(|lambda| lambda.defaults.iter().chain(std::iter::once(&lambda.body)));
+    let mains = prog.globals.iter().chain(std::iter::once(&prog.main));
+    for body in funcs.chain(lambdas).chain(mains.clone()) {
+        find_assigned(body, &mut checker.assigned);
+    }
+
+    // A function that always fails can make its callers always fail, so the function bodies are
+    // checked again until no more of them are found to always fail
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (id, func) in prog.funcs.iter().enumerate() {
+            if checker.funcs[id].is_none() && checker.check_expr(&func.body) == Known::Fails {
+                checker.funcs[id] = Some(std::mem::take(&mut checker.failures));
+                changed = true;
+            }
+            checker.failures.clear();
+        }
+    }
+
+    // check_expr only keeps the failures of expressions that always fail
+    for body in mains {
+        checker.check_expr(body);
+    }
+    let mut failures : Vec<Warning> = Vec::new();
+    for failure in checker.failures {
+        if !failures.contains(&failure) {
+            failures.push(failure);
+        }
+    }
+    failures
+}
+
+/// Returns whether a loop body can break out of its loop (breaks in nested loops do not count)
+fn has_break(e: &RExpr) -> bool {
+    match &e.kind {
+        RExprKind::Break(_) => true,
+        RExprKind::Loop(_) | RExprKind::Number(_) | RExprKind::Boolean(_) | RExprKind::Str(_) | RExprKind::Input | RExprKind::Args |
+        RExprKind::Var(_) | RExprKind::Global(_) | RExprKind::Lambda(_) | RExprKind::FuncRef(_) | RExprKind::MissingArg |
+        RExprKind::Error => false,
+        RExprKind::Let(binds, body) => binds.iter().any(|(_, expr)| has_break(expr)) || has_break(body),
+        RExprKind::LetRec(_, body) => has_break(body),
+        RExprKind::Set(_, expr) | RExprKind::SetGlobal(_, expr) | RExprKind::UnOp(_, expr) | RExprKind::StructIs(_, expr) |
+        RExprKind::StructGet(_, _, expr) | RExprKind::Raise(expr) | RExprKind::Panic(expr, _) => has_break(expr),
+        RExprKind::BinOp(_, e1, e2) | RExprKind::TInit(e1, e2) | RExprKind::TGet(e1, e2) |
+        RExprKind::StructSet(_, _, e1, e2) | RExprKind::And(e1, e2) | RExprKind::Or(e1, e2) |
+        RExprKind::Try(e1, _, e2) | RExprKind::Assert(e1, e2, _) | RExprKind::ApplyTuple(e1, e2) => has_break(e1) || has_break(e2),
+        RExprKind::If(e1, e2, e3) | RExprKind::TSet(e1, e2, e3) | RExprKind::Substring(e1, e2, e3) |
+        RExprKind::TSlice(e1, e2, e3) => {
+            has_break(e1) || has_break(e2) || has_break(e3)
+        },
+        RExprKind::Tuple(exprs) | RExprKind::Block(exprs) | RExprKind::Call(_, exprs) |
+        RExprKind::StructNew(_, exprs) => exprs.iter().any(has_break),
+        RExprKind::CallIndirect(callee, exprs) => has_break(callee) || exprs.iter().any(has_break),
+        RExprKind::Match(expr, cases) => has_break(expr) || cases.iter().any(|(_, body)| has_break(body)),
+        RExprKind::Cond(clauses, e_else) => {
+            clauses.iter().any(|(test, body)| has_break(test) || has_break(body)) || has_break(e_else)
+        },
+    }
+}
+
+/// Marks every binding that is the target of a set! in an expression (lambda bodies are visited separately)
+fn find_assigned(e: &RExpr, assigned: &mut [bool]) {
+    match &e.kind {
+        RExprKind::Number(_) | RExprKind::Boolean(_) | RExprKind::Str(_) | RExprKind::Input | RExprKind::Args | RExprKind::Var(_) |
+        RExprKind::Global(_) | RExprKind::Lambda(_) | RExprKind::FuncRef(_) | RExprKind::MissingArg | RExprKind::Error => {},
+        RExprKind::Set(id, expr) => {
+            assigned[*id] = true;
+            find_assigned(expr, assigned);
+        },
+        RExprKind::Let(binds, body) => {
+            for (_, expr) in binds {
+                find_assigned(expr, assigned);
+            }
+            find_assigned(body, assigned);
+        },
+        RExprKind::LetRec(_, body) => find_assigned(body, assigned),
+        RExprKind::UnOp(_, expr) | RExprKind::Loop(expr) | RExprKind::Break(expr) | RExprKind::StructIs(_, expr) |
+        RExprKind::StructGet(_, _, expr) | RExprKind::SetGlobal(_, expr) | RExprKind::Raise(expr) |
+        RExprKind::Panic(expr, _) => find_assigned(expr, assigned),
+        RExprKind::BinOp(_, e1, e2) | RExprKind::TInit(e1, e2) | RExprKind::TGet(e1, e2) | RExprKind::StructSet(_, _, e1, e2) |
+        RExprKind::And(e1, e2) | RExprKind::Or(e1, e2) | RExprKind::Try(e1, _, e2) | RExprKind::Assert(e1, e2, _) |
+        RExprKind::ApplyTuple(e1, e2) => {
+            find_assigned(e1, assigned);
+            find_assigned(e2, assigned);
+        },
+        RExprKind::If(e1, e2, e3) | RExprKind::TSet(e1, e2, e3) | RExprKind::Substring(e1, e2, e3) |
+        RExprKind::TSlice(e1, e2, e3) => {
+            find_assigned(e1, assigned);
+            find_assigned(e2, assigned);
+            find_assigned(e3, assigned);
+        },
+        RExprKind::Tuple(exprs) | RExprKind::Block(exprs) | RExprKind::Call(_, exprs) | RExprKind::StructNew(_, exprs) => {
+            for expr in exprs {
+                find_assigned(expr, assigned);
+            }
+        },
+        RExprKind::CallIndirect(callee, exprs) => {
+            find_assigned(callee, assigned);
+            for expr in exprs {
+                find_assigned(expr, assigned);
+            }
+        },
+        RExprKind::Cond(clauses, e_else) => {
+            for (test, body) in clauses {
+                find_assigned(test, assigned);
+                find_assigned(body, assigned);
+            }
+            find_assigned(e_else, assigned);
+        },
+        RExprKind::Match(expr, cases) => {
+            find_assigned(expr, assigned);
+            for (_, body) in cases {
+                find_assigned(body, assigned);
+            }
+        },
+    }
+}
+
+impl<'a> KindChecker<'a> {
+    /// Checks an argument of an operation against the kind its runtime check expects.
+    /// Returns whether the check always fails, recording the operation if so.
+    fn expect(&mut self, op: String, e: &RExpr, arg: &RExpr, found: Known, expected: ValueKind) -> bool {
+        match found {
+            Known::Kind(found) if found != expected => {
+                self.failures.push(Warning::DefiniteFailure { op, expected, found, span: e.span, arg: arg.span });
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Checks a list of expressions evaluated in order
+    fn check_exprs(&mut self, exprs: &[RExpr]) -> Vec<Known> {
+        exprs.iter().map(|e| self.check_expr(e)).collect()
+    }
+
+    /// Checks an expression, returning what is known about its value. The failing operations
+    /// found inside are only kept if the expression itself always fails.
+    fn check_expr(&mut self, e: &RExpr) -> Known {
+        let before = self.failures.len();
+        let known = self.check_kind(e);
+        if known != Known::Fails {
+            self.failures.truncate(before);
+        }
+        known
+    }
+
+    /// Checks the sub-expressions of an expression and the kinds of its operands. Operands are
+    /// checked in the order the compiled code evaluates and type checks them.
+    fn check_kind(&mut self, e: &RExpr) -> Known {
+        match &e.kind {
+            RExprKind::Number(_) => Known::Kind(ValueKind::Number),
+            RExprKind::Boolean(_) => Known::Kind(ValueKind::Boolean),
+            RExprKind::Str(_) => Known::Kind(ValueKind::String),
+            RExprKind::Input | RExprKind::Global(_) | RExprKind::MissingArg | RExprKind::Error => Known::Any,
+            RExprKind::Args => Known::Kind(ValueKind::Tuple),
+            RExprKind::Var(id) => self.kinds[*id],
+            RExprKind::Lambda(_) | RExprKind::FuncRef(_) => Known::Kind(ValueKind::Function),
+            RExprKind::Tuple(exprs) => {
+                let elems = self.check_exprs(exprs);
+                Known::after(&elems, Known::Kind(ValueKind::Tuple))
+            },
+            RExprKind::Let(binds, body) => {
+                let mut values : Vec<Known> = Vec::new();
+                for (id, expr) in binds {
+                    let value = self.check_expr(expr);
+                    if !self.assigned[*id] {
+                        self.kinds[*id] = value;
+                    }
+                    values.push(value);
+                }
+                let result = self.check_expr(body);
+                Known::after(&values, result)
+            },
+            RExprKind::LetRec(funcs, body) => {
+                for (id, _) in funcs {
+                    if !self.assigned[*id] {
+                        self.kinds[*id] = Known::Kind(ValueKind::Function);
+                    }
+                }
+                self.check_expr(body)
+            },
+            RExprKind::UnOp(op, expr) => {
+                let value = self.check_expr(expr);
+                match op {
+                    Op1::Add1 | Op1::Sub1 | Op1::BitNot if self.expect(op.to_string(), e, expr, value, ValueKind::Number) => Known::Fails,
+                    Op1::Add1 | Op1::Sub1 | Op1::BitNot => Known::after(&[value], Known::Kind(ValueKind::Number)),
+                    Op1::IsNum | Op1::IsBool | Op1::IsTuple => Known::after(&[value], Known::Kind(ValueKind::Boolean)),
+                    Op1::Not if self.expect(op.to_string(), e, expr, value, ValueKind::Boolean) => Known::Fails,
+                    Op1::Not => Known::after(&[value], Known::Kind(ValueKind::Boolean)),
+                    Op1::Print => value,
+                    Op1::StringLength | Op1::StringToNumber if self.expect(op.to_string(), e, expr, value, ValueKind::String) => Known::Fails,
+                    Op1::NumberToString if self.expect(op.to_string(), e, expr, value, ValueKind::Number) => Known::Fails,
+                    Op1::StringLength => Known::after(&[value], Known::Kind(ValueKind::Number)),
+                    Op1::NumberToString => Known::after(&[value], Known::Kind(ValueKind::String)),
+                    // Strings that are not numbers convert to false
+                    Op1::StringToNumber => Known::after(&[value], Known::Any),
+                    Op1::TupleLength | Op1::TupleCopy if self.expect(op.to_string(), e, expr, value, ValueKind::Tuple) => Known::Fails,
+                    Op1::TupleLength => Known::after(&[value], Known::Kind(ValueKind::Number)),
+                    Op1::TupleCopy => Known::after(&[value], Known::Kind(ValueKind::Tuple)),
+                }
+            },
+            RExprKind::BinOp(op, e1, e2) => {
+                let v2 = self.check_expr(e2);
+                let v1 = self.check_expr(e1);
+                if !v2.is_value() || !v1.is_value() {
+                    return Known::after(&[v2, v1], Known::Any);
+                }
+                let fails = match (op.get_type(), v1) {
+                    // Equality only fails on values of different kinds
+                    (Op2Type::Equality, Known::Kind(kind)) => self.expect(op.to_string(), e, e2, v2, kind),
+                    (Op2Type::Equality, _) | (Op2Type::Structural, _) => false,
+                    (Op2Type::String, _) => self.expect(op.to_string(), e, e1, v1, ValueKind::String)
+                        || self.expect(op.to_string(), e, e2, v2, ValueKind::String),
+                    (Op2Type::Tuple, _) => self.expect(op.to_string(), e, e1, v1, ValueKind::Tuple)
+                        || self.expect(op.to_string(), e, e2, v2, ValueKind::Tuple),
+                    _ => self.expect(op.to_string(), e, e1, v1, ValueKind::Number)
+                        || self.expect(op.to_string(), e, e2, v2, ValueKind::Number),
+                };
+                match op.get_type() {
+                    _ if fails => Known::Fails,
+                    Op2Type::Arithmetic => Known::Kind(ValueKind::Number),
+                    Op2Type::String if matches!(op, Op2::StringAppend) => Known::Kind(ValueKind::String),
+                    Op2Type::Tuple => Known::Kind(ValueKind::Tuple),
+                    _ => Known::Kind(ValueKind::Boolean),
+                }
+            },
+            RExprKind::If(cond, e1, e2) => {
+                let cond = self.check_expr(cond);
+                let v1 = self.check_expr(e1);
+                let v2 = self.check_expr(e2);
+                Known::after(&[cond], v1.join(v2))
+            },
+            // The second operand is not always evaluated, so it never always fails. In tail position
+            // its value is returned unchecked.
+            RExprKind::And(e1, e2) | RExprKind::Or(e1, e2) => {
+                let op = if matches!(e.kind, RExprKind::And(..)) { "and" } else { "or" };
+                let v1 = self.check_expr(e1);
+                if !v1.is_value() || self.expect(op.to_string(), e, e1, v1, ValueKind::Boolean) {
+                    return Known::after(&[v1], Known::Fails);
+                }
+                let v2 = self.check_expr(e2);
+                Known::Kind(ValueKind::Boolean).join(v2)
+            },
+            // Only the first test is always evaluated
+            RExprKind::Cond(clauses, e_else) => {
+                let mut result : Option<Known> = None;
+                for (i, (test, body)) in clauses.iter().enumerate() {
+                    let test = self.check_expr(test);
+                    if i == 0 && !test.is_value() {
+                        return test;
+                    }
+                    let known = self.check_expr(body);
+                    result = Some(result.map_or(known, |result| result.join(known)));
+                }
+                let known = self.check_expr(e_else);
+                result.map_or(known, |result| result.join(known))
+            },
+            // A loop without a break always fails if its body does
+            RExprKind::Loop(body) => match self.check_expr(body) {
+                Known::Fails if !has_break(body) => Known::Fails,
+                _ => Known::Any,
+            },
+            RExprKind::Break(expr) => Known::after(&[self.check_expr(expr)], Known::Exits),
+            RExprKind::Raise(expr) | RExprKind::Panic(expr, _) => Known::after(&[self.check_expr(expr)], Known::Exits),
+            // The message is only evaluated when the assertion fails
+            RExprKind::Assert(..) if self.release => Known::Kind(ValueKind::Boolean),
+            RExprKind::Assert(cond, msg, _) => {
+                let cond = self.check_expr(cond);
+                self.check_expr(msg);
+                Known::after(&[cond], Known::Kind(ValueKind::Boolean))
+            },
+            // Failures in the body are caught by the handler, which sees a value of any kind
+            RExprKind::Try(body, id, handler) => {
+                let before = self.failures.len();
+                let known = self.check_expr(body);
+                self.failures.truncate(before);
+                self.kinds[*id] = Known::Any;
+                let handled = self.check_expr(handler);
+                if known == Known::Fails { handled } else { known.join(handled) }
+            },
+            RExprKind::Set(_, expr) | RExprKind::SetGlobal(_, expr) => self.check_expr(expr),
+            RExprKind::TInit(e_length, e_value) => {
+                let value = self.check_expr(e_value);
+                let length = self.check_expr(e_length);
+                if !value.is_value() || !length.is_value() {
+                    return Known::after(&[value, length], Known::Any);
+                }
+                if self.expect(String::from("tinit"), e, e_length, length, ValueKind::Number) {
+                    return Known::Fails;
+                }
+                Known::Kind(ValueKind::Tuple)
+            },
+            RExprKind::TSet(e_tuple, e_index, e_value) => {
+                let index = self.check_expr(e_index);
+                if !index.is_value() || self.expect(String::from("tset"), e, e_index, index, ValueKind::Number) {
+                    return Known::after(&[index], Known::Fails);
+                }
+                let value = self.check_expr(e_value);
+                let tuple = self.check_expr(e_tuple);
+                if !value.is_value() || !tuple.is_value() {
+                    return Known::after(&[value, tuple], Known::Any);
+                }
+                if self.expect(String::from("tset"), e, e_tuple, tuple, ValueKind::Tuple) {
+                    return Known::Fails;
+                }
+                Known::Kind(ValueKind::Tuple)
+            },
+            // (arg i) reads the tuple of the command-line arguments
+            RExprKind::TGet(e_tuple, e_index) => {
+                let op = if matches!(e_tuple.kind, RExprKind::Args) { "arg" } else { "tget" };
+                let index = self.check_expr(e_index);
+                if !index.is_value() || self.expect(op.to_string(), e, e_index, index, ValueKind::Number) {
+                    return Known::after(&[index], Known::Fails);
+                }
+                let tuple = self.check_expr(e_tuple);
+                if !tuple.is_value() || self.expect(String::from("tget"), e, e_tuple, tuple, ValueKind::Tuple) {
+                    return Known::after(&[tuple], Known::Fails);
+                }
+                Known::Any
+            },
+            RExprKind::Substring(e_string, e_start, e_end) => {
+                let string = self.check_expr(e_string);
+                let start = self.check_expr(e_start);
+                let end = self.check_expr(e_end);
+                if !string.is_value() || !start.is_value() || !end.is_value() {
+                    return Known::after(&[string, start, end], Known::Any);
+                }
+                if self.expect(String::from("substring"), e, e_string, string, ValueKind::String)
+                    || self.expect(String::from("substring"), e, e_start, start, ValueKind::Number)
+                    || self.expect(String::from("substring"), e, e_end, end, ValueKind::Number) {
+                    return Known::Fails;
+                }
+                Known::Kind(ValueKind::String)
+            },
+            RExprKind::TSlice(e_tuple, e_start, e_end) => {
+                let tuple = self.check_expr(e_tuple);
+                let start = self.check_expr(e_start);
+                let end = self.check_expr(e_end);
+                if !tuple.is_value() || !start.is_value() || !end.is_value() {
+                    return Known::after(&[tuple, start, end], Known::Any);
+                }
+                if self.expect(String::from("tslice"), e, e_tuple, tuple, ValueKind::Tuple)
+                    || self.expect(String::from("tslice"), e, e_start, start, ValueKind::Number)
+                    || self.expect(String::from("tslice"), e, e_end, end, ValueKind::Number) {
+                    return Known::Fails;
+                }
+                Known::Kind(ValueKind::Tuple)
+            },
+            RExprKind::StructNew(_, exprs) => {
+                let fields = self.check_exprs(exprs);
+                Known::after(&fields, Known::Kind(ValueKind::Record))
+            },
+            // Which struct a record belongs to is not tracked, only that it is a record
+            RExprKind::StructIs(_, expr) => Known::after(&[self.check_expr(expr)], Known::Kind(ValueKind::Boolean)),
+            RExprKind::StructGet(sid, field, e_record) => {
+                let record = self.check_expr(e_record);
+                let op = self.structs[*sid].accessor(*field);
+                if !record.is_value() || self.expect(op, e, e_record, record, ValueKind::Record) {
+                    return Known::after(&[record], Known::Fails);
+                }
+                Known::Any
+            },
+            RExprKind::StructSet(sid, field, e_record, e_value) => {
+                let record = self.check_expr(e_record);
+                let value = self.check_expr(e_value);
+                if !record.is_value() || !value.is_value() {
+                    return Known::after(&[record, value], Known::Any);
+                }
+                let op = self.structs[*sid].mutator(*field);
+                if self.expect(op, e, e_record, record, ValueKind::Record) {
+                    return Known::Fails;
+                }
+                Known::Kind(ValueKind::Record)
+            },
+            // A variable pattern on its own takes the value being matched, other pattern
+            // variables hold parts of a tuple. A match without any matching case fails at
+            // runtime, which is not known statically.
+            RExprKind::Match(expr, cases) => {
+                let value = self.check_expr(expr);
+                if !value.is_value() {
+                    return value;
+                }
+                let mut result : Option<Known> = None;
+                for (pattern, body) in cases {
+                    if let RPattern::Var(id) = pattern {
+                        if !self.assigned[*id] {
+                            self.kinds[*id] = value;
+                        }
+                    }
+                    let known = self.check_expr(body);
+                    result = Some(result.map_or(known, |result| result.join(known)));
+                }
+                result.unwrap_or(Known::Any)
+            },
+            RExprKind::Block(exprs) => {
+                let values = self.check_exprs(exprs);
+                Known::after(&values, *values.last().unwrap_or(&Known::Any))
+            },
+            // The arguments are evaluated before the body of the function runs
+            RExprKind::Call(id, exprs) => {
+                let args = self.check_exprs(exprs);
+                match &self.funcs[*id] {
+                    Some(failures) if Known::after(&args, Known::Any).is_value() => {
+                        self.failures.extend(failures.clone());
+                        Known::Fails
+                    },
+                    _ => Known::after(&args, Known::Any),
+                }
+            },
+            // The callee is evaluated first and checked after the arguments
+            RExprKind::CallIndirect(callee, exprs) => {
+                let function = self.check_expr(callee);
+                if !function.is_value() {
+                    return function;
+                }
+                let args = self.check_exprs(exprs);
+                if !Known::after(&args, function).is_value() {
+                    return Known::after(&args, Known::Any);
+                }
+                if self.expect(String::from("call"), e, callee, function, ValueKind::Function) {
+                    return Known::Fails;
+                }
+                Known::Any
+            },
+            RExprKind::ApplyTuple(e_func, e_tuple) => {
+                let function = self.check_expr(e_func);
+                if !function.is_value() {
+                    return function;
+                }
+                let tuple = self.check_expr(e_tuple);
+                if !tuple.is_value() || self.expect(String::from("apply"), e, e_tuple, tuple, ValueKind::Tuple)
+                    || self.expect(String::from("apply"), e, e_func, function, ValueKind::Function) {
+                    return Known::after(&[tuple], Known::Fails);
+                }
+                Known::Any
+            },
+        }
+    }
+}
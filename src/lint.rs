@@ -0,0 +1,143 @@
+/*
+    lint.rs
+
+    Lint pass over a resolved program: finds unused let bindings and parameters, functions
+    that cannot be reached from the main expression, and let bindings that shadow outer names.
+*/
+
+use crate::types::*;
+
+use std::collections::HashSet;
+
+/// Lints every function and the main expression, returning all warnings found
+pub fn lint_program(prog: &RProgram) -> Vec<Warning> {
+    let mut warnings : Vec<Warning> = Vec::new();
+    let mut used : Vec<bool> = vec![false; prog.bindings.len()];
+
+    // Functions called directly by each function and by main
+    let mut call_graph : Vec<HashSet<FuncId>> = Vec::new();
+    for func in &prog.funcs {
+        let mut calls : HashSet<FuncId> = HashSet::new();
+        for default in &func.defaults {
+            lint_expr(default, &prog.lambdas, &mut used, &mut calls);
+        }
+        lint_expr(&func.body, &prog.lambdas, &mut used, &mut calls);
+        call_graph.push(calls);
+    }
+    // Global initializers run before main, so the functions they call are reached too
+    let mut main_calls : HashSet<FuncId> = HashSet::new();
+    for global in &prog.globals {
+        lint_expr(global, &prog.lambdas, &mut used, &mut main_calls);
+    }
+    lint_expr(&prog.main, &prog.lambdas, &mut used, &mut main_calls);
+
+    for (id, bind) in prog.bindings.iter().enumerate() {
+        if !used[id] {
+            let name = bind.name.clone();
+            warnings.push(match bind.kind {
+                BindKind::Param(_) => Warning::UnusedParameter(name, bind.span),
+                BindKind::Local => Warning::UnusedBinding(name, bind.span),
+            });
+        }
+        if let Some(outer) = bind.shadows {
+            warnings.push(Warning::ShadowedBinding {
+                name: bind.name.clone(), span: bind.span, outer: prog.bindings[outer].span,
+            });
+        }
+    }
+
+    // Functions reachable from main through any chain of calls
+    let mut reached : HashSet<FuncId> = HashSet::new();
+    let mut pending : Vec<FuncId> = main_calls.into_iter().collect();
+    while let Some(fid) = pending.pop() {
+        if reached.insert(fid) {
+            pending.extend(call_graph[fid].iter().copied());
+        }
+    }
+    for (fid, func) in prog.funcs.iter().enumerate() {
+        if !reached.contains(&fid) {
+            warnings.push(Warning::UnusedFunction(func.name.clone(), func.name_span));
+        }
+    }
+    warnings
+}
+
+/// Marks every binding read by an expression as used and collects the functions it calls or
+/// refers to, including from the bodies of the lambdas it creates
+fn lint_expr(e: &RExpr, lambdas: &[RLambda], used: &mut [bool], calls: &mut HashSet<FuncId>) {
+    match &e.kind {
+        RExprKind::Number(_) | RExprKind::Boolean(_) | RExprKind::Str(_) | RExprKind::Input | RExprKind::Args | RExprKind::Global(_) |
+        RExprKind::MissingArg | RExprKind::Error => {},
+        RExprKind::Var(id) => used[*id] = true,
+        RExprKind::FuncRef(fid) => { calls.insert(*fid); },
+        RExprKind::Lambda(lid) => lint_lambda(&lambdas[*lid], lambdas, used, calls),
+        RExprKind::Let(binds, body) => {
+            for (_, expr) in binds {
+                lint_expr(expr, lambdas, used, calls);
+            }
+            lint_expr(body, lambdas, used, calls);
+        },
+        RExprKind::LetRec(funcs, body) => {
+            for (_, lid) in funcs {
+                lint_lambda(&lambdas[*lid], lambdas, used, calls);
+            }
+            lint_expr(body, lambdas, used, calls);
+        },
+        // Assigning to a variable does not count as reading it
+        RExprKind::Set(_, expr) | RExprKind::SetGlobal(_, expr) | RExprKind::UnOp(_, expr) | RExprKind::Loop(expr) |
+        RExprKind::Break(expr) | RExprKind::Raise(expr) | RExprKind::Panic(expr, _) | RExprKind::StructIs(_, expr) |
+        RExprKind::StructGet(_, _, expr) => {
+            lint_expr(expr, lambdas, used, calls);
+        },
+        RExprKind::Call(fid, exprs) => {
+            calls.insert(*fid);
+            for expr in exprs {
+                lint_expr(expr, lambdas, used, calls);
+            }
+        },
+        RExprKind::CallIndirect(callee, exprs) => {
+            lint_expr(callee, lambdas, used, calls);
+            for expr in exprs {
+                lint_expr(expr, lambdas, used, calls);
+            }
+        },
+        RExprKind::BinOp(_, e1, e2) | RExprKind::TInit(e1, e2) | RExprKind::TGet(e1, e2) | RExprKind::StructSet(_, _, e1, e2) |
+        RExprKind::And(e1, e2) | RExprKind::Or(e1, e2) | RExprKind::Try(e1, _, e2) | RExprKind::Assert(e1, e2, _) |
+        RExprKind::ApplyTuple(e1, e2) => {
+            lint_expr(e1, lambdas, used, calls);
+            lint_expr(e2, lambdas, used, calls);
+        },
+        RExprKind::If(e1, e2, e3) | RExprKind::TSet(e1, e2, e3) | RExprKind::Substring(e1, e2, e3) |
+        RExprKind::TSlice(e1, e2, e3) => {
+            lint_expr(e1, lambdas, used, calls);
+            lint_expr(e2, lambdas, used, calls);
+            lint_expr(e3, lambdas, used, calls);
+        },
+        RExprKind::Cond(clauses, e_else) => {
+            for (test, body) in clauses {
+                lint_expr(test, lambdas, used, calls);
+                lint_expr(body, lambdas, used, calls);
+            }
+            lint_expr(e_else, lambdas, used, calls);
+        },
+        RExprKind::Match(expr, cases) => {
+            lint_expr(expr, lambdas, used, calls);
+            for (_, body) in cases {
+                lint_expr(body, lambdas, used, calls);
+            }
+        },
+        RExprKind::Tuple(exprs) | RExprKind::Block(exprs) | RExprKind::StructNew(_, exprs) => {
+            for expr in exprs {
+                lint_expr(expr, lambdas, used, calls);
+            }
+        },
+    }
+}
+
+/// Lints the default values of the parameters of a lambda, then its body
+fn lint_lambda(lambda: &RLambda, lambdas: &[RLambda], used: &mut [bool], calls: &mut HashSet<FuncId>) {
+    for default in &lambda.defaults {
+        lint_expr(default, lambdas, used, calls);
+    }
+    lint_expr(&lambda.body, lambdas, used, calls);
+}
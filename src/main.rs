@@ -7,43 +7,173 @@
 
 mod types;
 mod utils;
+mod reader;
 mod parser;
+mod resolver;
+mod lint;
+mod kinds;
 mod compiler;
+mod diagnostics;
+mod explain;
 
 #[macro_use]
 extern crate lazy_static;
 
 use std::env;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 
-use sexp::*;
-
+use crate::reader::*;
 use crate::parser::*;
+use crate::resolver::*;
+use crate::lint::*;
+use crate::kinds::*;
 use crate::compiler::*;
+use crate::diagnostics::*;
+use crate::explain::*;
 use crate::types::*;
+use crate::utils::*;
+
+/// Assembly for the functions, for the main expression and for the data section
+type Assembly = (String, String, String);
+
+/// Parses and compiles Snek source text into the assembly for its functions, main expression and data,
+/// along with the warnings to report. On failure, returns every static error found together with
+/// the warnings. Lints missing from levels are reported at their default level, and release builds
+/// leave out assertions.
+fn compile_source(source: &str, levels: &HashMap<Lint, LintLevel>, release: bool)
+    -> Result<(Assembly, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let (sexps, span) = read_program(source).map_err(|err| vec![err.to_diagnostic()])?;
+
+    // Parse and resolve the program, collecting all errors before giving up
+    let mut errs : Vec<CompileError> = Vec::new();
+    let prog : Program = parse_program(&sexps, span, &mut errs);
+    let prog : RProgram = resolve_program(&prog, &mut errs);
+    let mut diags : Vec<Diagnostic> = errs.iter().map(|err| err.to_diagnostic()).collect();
+
+    // Lints only run on well-formed programs, and denied lints fail the compilation
+    if errs.is_empty() {
+        for warning in lint_program(&prog).into_iter().chain(check_kinds(&prog, release)) {
+            let level = levels.get(&warning.lint()).copied().unwrap_or(warning.lint().default_level());
+            if level != LintLevel::Allow {
+                diags.push(warning.to_diagnostic(level));
+            }
+        }
+    }
+    diags.sort_by_key(|diag| diag.span.start);
+    if diags.iter().any(|diag| diag.severity == Severity::Error) {
+        return Err(diags);
+    }
+
+    // Compiles resolved contents into assembly instructions
+    Ok((compile(&prog, release), diags))
+}
 
-/// Compiles a .snek file into an x86 assembly .s file.
+/// Command-line options of the compiler
+struct Options {
+    in_name : String,
+    out_name : String,
+    error_format : ErrorFormat,
+    lint_levels : HashMap<Lint, LintLevel>,
+    release : bool,
+}
+
+/// Lint level set by a -W (warn), -A (allow) or -D (deny) flag
+fn lint_flag_level(arg: &str) -> Option<LintLevel> {
+    match arg.get(..2)? {
+        "-W" => Some(LintLevel::Warn),
+        "-A" => Some(LintLevel::Allow),
+        "-D" => Some(LintLevel::Deny),
+        _ => None,
+    }
+}
+
+/// Parses the command-line arguments: flags followed by the input and output file names
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut error_format = ErrorFormat::Human;
+    let mut lint_levels : HashMap<Lint, LintLevel> = HashMap::new();
+    let mut release = false;
+    let mut files : Vec<String> = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(format) = arg.strip_prefix("--error-format=") {
+            error_format = match format {
+                "human" => ErrorFormat::Human,
+                "json" => ErrorFormat::Json,
+                _ => return Err(format!("unknown error format '{format}' (expected 'human' or 'json')")),
+            };
+        } else if arg == "--release" {
+            release = true;
+        } else if let Some(level) = lint_flag_level(arg) {
+            // Lint name either attached (-Wname) or as the next argument (-W name)
+            let name = match &arg[2..] {
+                "" => args.next().ok_or(format!("missing lint name after '{arg}'"))?.as_str(),
+                name => name,
+            };
+            let lint = Lint::from_name(name).ok_or(format!("unknown lint '{name}'"))?;
+            lint_levels.insert(lint, level);
+        } else if arg.starts_with('-') {
+            return Err(format!("unknown option '{arg}'"));
+        } else {
+            files.push(arg.clone());
+        }
+    }
+    match <[String; 2]>::try_from(files) {
+        Ok([in_name, out_name]) => Ok(Options { in_name, out_name, error_format, lint_levels, release }),
+        Err(_) => Err(String::from("expected an input .snek file and an output .s file")),
+    }
+}
+
+/// Compiles a .snek file into an x86 assembly .s file, or explains an error code
+/// with --explain <code>.
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
-
-    let in_name = &args[1];
-    let out_name = &args[2];
+    if let [_, flag, code] = &args[..] {
+        if flag == "--explain" {
+            match explain(code) {
+                Some(text) => print!("{text}"),
+                None => {
+                    eprintln!("error: '{code}' is not a diamondback error or lint code");
+                    std::process::exit(2);
+                }
+            }
+            return Ok(());
+        }
+    }
+    let opts = match parse_args(&args[1..]) {
+        Ok(opts) => opts,
+        Err(msg) => {
+            let lints = Lint::ALL.map(|lint| lint.name()).join(", ");
+            eprintln!("error: {msg}");
+            eprintln!("usage: {} [--error-format=human|json] [--release] [-W|-A|-D <lint>]... <input.snek> <output.s>", args[0]);
+            eprintln!("       {} --explain <code>", args[0]);
+            eprintln!("lints: {lints}");
+            std::process::exit(2);
+        }
+    };
+    let in_name = &opts.in_name;
+    let out_name = &opts.out_name;
 
     // Parses .snek file
     let mut in_file = File::open(in_name)?;
     let mut in_contents = String::new();
     in_file.read_to_string(&mut in_contents)?;
-    let sexp_str = format!("({})", in_contents);
-    let prog : Program;
-    if let Ok(parsed) = &parse(&sexp_str) {
-        prog = parse_program(parsed.clone());
-    } else {
-        panic!("Invalid");
-    }
-    
-    // Compiles parsed contents into assembly instructions
-    let (functions, result) = compile(&prog);
+    let src = SourceFile { name: in_name, text: &in_contents };
+    let (functions, result, strings) = match compile_source(&in_contents, &opts.lint_levels, opts.release) {
+        Ok((asm, warnings)) => {
+            report(&warnings, &src, opts.error_format);
+            asm
+        },
+        Err(diags) => {
+            // Reports the static errors and exits without writing any assembly
+            report(&diags, &src, opts.error_format);
+            std::process::exit(1);
+        }
+    };
+
+    // Source file name as a NUL-terminated byte string for runtime error reports
+    let src_name = in_name.bytes().chain([0]).map(|b| b.to_string()).collect::<Vec<_>>().join(", ");
 
     // Base assembly format with new assembly instructions
     // for functions and main expression
@@ -51,19 +181,57 @@ fn main() -> std::io::Result<()> {
         "
 section .text
 extern snek_error
+extern snek_panic
 extern snek_print
+extern snek_number_to_string
+extern snek_string_to_number
+extern snek_string_append
+extern snek_string_equal
+extern snek_equal
+extern snek_substring
+extern snek_tuple_slice
+extern snek_tuple_append
 global our_code_starts_here
 throw_error_align:
+  mov rcx, [rel snek_handler]
+  test rcx, rcx
+  jz snek_fatal_error
+  sub rdx, {MSMX_ERRCODE}
+  shl rdx, 4
+  lea rax, [rel snek_error_codes]
+  add rax, rdx
+  add rax, {STRING_TAG}
+  jmp snek_unwind
+snek_raise:
+  mov rcx, [rel snek_handler]
+  test rcx, rcx
+  jnz snek_unwind
+  mov rdx, {RAISE_ERRCODE}
+snek_fatal_error:
   sub rsp, 8
   mov rdi, rdx
+  lea rdx, [rel snek_src_file]
+  mov rcx, rax
   call snek_error
   add rsp, 8
+snek_unwind:
+  mov rsp, [rcx - 16]
+  mov rbp, [rcx - 24]
+  mov rbx, [rcx]
+  mov [rel snek_handler], rbx
+  mov rcx, [rcx - 8]
+  jmp rcx
 {}
 our_code_starts_here:
   mov r15, rsi
+  add r15, 15
+  and r15, -16
 {}
   ret
-", functions, result);
+
+section .data
+snek_src_file: db {}
+{}", functions, result, src_name, strings);
 
     // Writes out assembly program contents to file
     let mut out_file = File::create(out_name)?;
@@ -7,299 +7,681 @@
 use crate::types::*;
 use crate::utils::*;
 
-use sexp::Atom::*;
-use sexp::*;
+use crate::types::Atom::*;
 
-use std::collections::HashSet;
+/// Parses the s-expressions comprising the program into an abstract program structure.
+/// Errors are collected into errs and parsing continues past malformed definitions and
+/// expressions, which are replaced by ExprKind::Error nodes.
+pub fn parse_program(sexps : &[Sexp], span : Span, errs : &mut Vec<CompileError>) -> Program {
+    // A program without any contents is invalid
+    if sexps.is_empty() {
+        errs.push(CompileError::EmptyProgram(span));
+        return Program { defns: Vec::new(), structs: Vec::new(), globals: Vec::new(), main: Expr { kind: ExprKind::Error, span }, span };
+    }
+    // A program must end with its main expression
+    let (defn_sexps, main) = match sexps.split_last() {
+        Some((main, defn_sexps)) if !is_defn(main) => (defn_sexps, Some(main)),
+        _ => {
+            errs.push(CompileError::MissingMain(sexps[sexps.len()-1].span()));
+            (sexps, None)
+        },
+    };
 
-/// Parses the s-expression comprising the program into an abstract program structure
-pub fn parse_program(sexps : Sexp) -> Program {
-    // Match the program s-exp
-    match sexps {
-        // Internal list of s-expressions comprising 
-        // each function definition and the main expression
-        Sexp::List(vec) => {
-            // A program without any contents is invalid
-            if vec.is_empty() {
-                panic!("Invalid");
-            }
-            
-            // Compile the function map via the function names
-            let mut defns : Vec<Function> = Vec::new();
-            let mut fnames : HashSet<String> = HashSet::new();
-            for i in 0..(vec.len()-1) {
-                let fname = parse_defn_name(&vec[i]);
-                if !is_valid_identifier(&fname) {
-                    panic!("Invalid function naming conventions");
-                }
-                if fnames.contains(&fname) {
-                    panic!("Duplicate function name");
-                }
-                if (*RESERVED).contains(&fname) {
-                    panic!("Invalid function definition - keyword");
-                }
-                fnames.insert(fname); // append to function map
+    // Parse the function, struct and global definitions and append to their lists
+    let mut defns : Vec<Function> = Vec::new();
+    let mut structs : Vec<Struct> = Vec::new();
+    let mut globals : Vec<Global> = Vec::new();
+    for defn in defn_sexps {
+        if is_struct_defn(defn) {
+            if let Some(st) = parse_struct(defn, errs) {
+                structs.push(st);
             }
-            
-            // Parse the function definitions and append to list
-            for i in 0..(vec.len()-1) {
-                defns.push(parse_defn(&vec[i], &fnames));
+            continue;
+        }
+        if is_global_defn(defn) {
+            if let Some(global) = parse_global(defn, errs) {
+                globals.push(global);
             }
+            continue;
+        }
+        if let Some(func) = parse_named_defn(defn, errs) {
+            defns.push(func);
+        }
+    }
+
+    // Return program structure with parsed main expression
+    let main = match main {
+        Some(main) => parse_expr(main, errs),
+        None => Expr { kind: ExprKind::Error, span: Span { start: span.end, end: span.end, ..span } },
+    };
+    Program { defns, structs, globals, main, span }
+}
+
+/// Determines whether an s-expression is a function, struct or global definition
+fn is_defn(s: &Sexp) -> bool {
+    is_fun_defn(s) || is_struct_defn(s) || is_global_defn(s)
+}
+
+/// Returns whether an s-expression is headed by the "fun" keyword
+fn is_fun_defn(s: &Sexp) -> bool {
+    matches!(s, Sexp::List(vec, _) if matches!(vec.first(), Some(Sexp::Atom(S(word), _)) if word == "fun"))
+}
+
+/// Determines whether an s-expression is a struct declaration
+fn is_struct_defn(s: &Sexp) -> bool {
+    matches!(s, Sexp::List(vec, _) if matches!(vec.first(), Some(Sexp::Atom(S(word), _)) if word == "struct"))
+}
 
-            // Return program structure with parsed main expression
-            Program { defns: defns, main : parse_expr(&vec[vec.len()-1], &fnames) }
+/// Determines whether an s-expression is a global definition
+fn is_global_defn(s: &Sexp) -> bool {
+    matches!(s, Sexp::List(vec, _) if matches!(vec.first(), Some(Sexp::Atom(S(word), _)) if word == "def"))
+}
+
+/// Checks that a struct, field or global name is a usable identifier, reporting it otherwise
+fn check_defn_name(name: &str, span: Span, errs: &mut Vec<CompileError>) {
+    if !is_valid_identifier(name) {
+        errs.push(CompileError::InvalidIdentifier(name.to_string(), span));
+    } else if (*RESERVED).iter().any(|word| word == name) {
+        errs.push(CompileError::KeywordMisuse(name.to_string(), span));
+    }
+}
+
+/// Parses a struct declaration (struct name (field ...)), or None if it is malformed
+fn parse_struct(s: &Sexp, errs: &mut Vec<CompileError>) -> Option<Struct> {
+    let (name, name_span, field_sexps) = match s {
+        Sexp::List(vec, _) => match &vec[..] {
+            [_, Sexp::Atom(S(name), name_span), Sexp::List(fields, _)] => (name, *name_span, fields),
+            _ => {
+                errs.push(CompileError::InvalidForm(String::from("struct"), s.span()));
+                return None;
+            },
         },
-        _ => panic!("Invalid - Must send sexp list with parentheses"),
+        _ => return None,
+    };
+    check_defn_name(name, name_span, errs);
+
+    let mut fields : Vec<Binder> = Vec::new();
+    for sexp in field_sexps {
+        if let Sexp::Atom(S(field), span) = sexp {
+            check_defn_name(field, *span, errs);
+            fields.push(Binder { name: field.to_string(), span: *span });
+        } else {
+            errs.push(CompileError::InvalidForm(String::from("struct"), s.span()));
+            return None;
+        }
     }
+    Some(Struct { name: name.to_string(), name_span, fields, span: s.span() })
 }
 
-/// Parses out the function name from a function s-expression
-pub fn parse_defn_name(s: &Sexp) -> String {
+/// Parses a global definition (def name expr), or None if it is malformed
+fn parse_global(s: &Sexp, errs: &mut Vec<CompileError>) -> Option<Global> {
+    match s {
+        Sexp::List(vec, _) => match &vec[..] {
+            [_, Sexp::Atom(S(name), name_span), body] => {
+                check_defn_name(name, *name_span, errs);
+                Some(Global { name: name.to_string(), name_span: *name_span, body: parse_expr(body, errs), span: s.span() })
+            },
+            _ => {
+                errs.push(CompileError::InvalidForm(String::from("def"), s.span()));
+                None
+            },
+        },
+        _ => None,
+    }
+}
+
+/// Parses out the function name and its span from a function s-expression
+pub fn parse_defn_name(s: &Sexp) -> Result<(String, Span), CompileError> {
     // Match the s-expression to a list
     match s {
-        Sexp::List(vec) => {
+        Sexp::List(vec, span) => {
             match &vec[..] {
                 // Match the list to [fun, definition-list, etc...]
-                [Sexp::Atom(S(fun_word)), Sexp::List(decl), _] if fun_word == "fun" => {
+                [Sexp::Atom(S(fun_word), _), Sexp::List(decl, _), _] if fun_word == "fun" => {
                     // Match out the first element string of the definition list
                     match &decl[..] {
-                        [Sexp::Atom(S(name)), ..] => {
-                            name.to_string() // Return function name
+                        [Sexp::Atom(S(name), name_span), ..] => {
+                            Ok((name.to_string(), *name_span)) // Return function name
                         },
-                        _ => panic!("Invalid"),
+                        _ => Err(CompileError::InvalidDefinition(*span)),
                     }
                 },
-                _ => panic!("Invalid"),
+                _ => Err(CompileError::InvalidDefinition(*span)),
             }
         },
-        _ => panic!("Invalid"),
+        Sexp::Atom(_, span) => Err(CompileError::InvalidDefinition(*span)),
     }
 }
 
-/// Parse complete function definition into abstract function structure
-pub fn parse_defn(s: &Sexp, fmap: &HashSet<String>) -> Function {
-    let fname : String;
-    let mut fargs : Vec<String> = Vec::new();           // list of function arguments
-    let mut fargset : HashSet<String> = HashSet::new(); // set  of function arguments:
-                                                        // used for checking duplicates
-    let fbody : Expr;
+/// Parse complete function definition into abstract function structure, or None if the
+/// definition is too malformed to have a name (errors already reported by parse_defn_name)
+pub fn parse_defn(s: &Sexp, errs: &mut Vec<CompileError>) -> Option<Function> {
+    // Match list to [fun, definition-list, expr-body]
+    let (name, name_span, args, body) = match s {
+        Sexp::List(vec, _) => match &vec[..] {
+            [Sexp::Atom(S(fun_word), _), Sexp::List(decl, _), body] if fun_word == "fun" => {
+                match &decl[..] {
+                    [Sexp::Atom(S(name), name_span), args @ ..] => (name, *name_span, args, body),
+                    _ => return None,
+                }
+            },
+            _ => return None,
+        },
+        _ => return None,
+    };
 
-    // Match function s-expression
-    match s {
-        Sexp::List(vec) => {
-            match &vec[..] {
-                // Match list to [fun, definition-list, expr-body]
-                [Sexp::Atom(S(fun_word)), Sexp::List(decl), body] if fun_word == "fun" => {
-                    fbody = parse_expr(body, fmap);     // parse main expression of function
-                    match &decl[..] {
-                        [Sexp::Atom(S(name)), args @ ..] => {
-                            fname = name.to_string();   // function name from first element
+    // Compile function arguments into the list and set
+    let (params, valid) = parse_params(args, errs);
+    if !valid {
+        errs.push(CompileError::InvalidDefinition(s.span()));
+    }
 
-                            // Compile function arguments into the list and set
-                            for sexp in args {
-                                if let Sexp::Atom(S(arg)) = sexp {
-                                    if !is_valid_identifier(arg) {
-                                        panic!("Invalid argument naming conventions");
-                                    }
-                                    if fargset.contains(arg) {
-                                        panic!("Duplicate parameter name");
-                                    }
-                                    if (*RESERVED).contains(arg) {
-                                        panic!("Invalid function definition - keyword");
-                                    }
-                                    fargs.push(arg.to_string());
-                                    fargset.insert(arg.to_string());
-                                } else {
-                                    panic!("Invalid");
-                                }
-                            }
-                        },
-                        _ => { panic!("Invalid"); },
-                    }
+    // Return function structure with the parsed main expression of the function
+    Some(Function { name: name.to_string(), name_span, params, body: parse_expr(body, errs), span: s.span() })
+}
+
+/// Parses the name of a parameter, reporting it in errs if it is invalid, or None if it is not a name
+fn parse_param(sexp: &Sexp, errs: &mut Vec<CompileError>) -> Option<Binder> {
+    let Sexp::Atom(S(name), span) = sexp else {
+        return None;
+    };
+    if !is_valid_identifier(name) {
+        errs.push(CompileError::InvalidIdentifier(name.to_string(), *span));
+    } else if (*RESERVED).contains(name) {
+        errs.push(CompileError::KeywordMisuse(name.to_string(), *span));
+    }
+    Some(Binder { name: name.to_string(), span: *span })
+}
+
+/// Parses the parameters of a function or lambda, where `(name default)` is an optional parameter,
+/// which may only be followed by other optional ones, and `. rest` after the other parameters
+/// binds the extra arguments of a call. Invalid names are reported in errs. Malformed parameters
+/// are left out, and the returned flag is false if there were any.
+fn parse_params(sexps: &[Sexp], errs: &mut Vec<CompileError>) -> (Params, bool) {
+    let (sexps, rest) = match sexps {
+        [params @ .., Sexp::Atom(S(dot), _), rest] if dot == "." => (params, Some(rest)),
+        _ => (sexps, None),
+    };
+    let mut valid = true;
+    let mut params = Params { fixed: Vec::new(), optional: Vec::new(), rest: None };
+    for sexp in sexps {
+        match sexp {
+            Sexp::List(vec, _) => match &vec[..] {
+                [name, default] => match parse_param(name, errs) {
+                    Some(param) => params.optional.push((param, parse_expr(default, errs))),
+                    None => valid = false,
                 },
-                _ => { panic!("Invalid"); },
-            }
-        },
-        _ => { panic!("Invalid"); },
+                _ => valid = false,
+            },
+            _ if !params.optional.is_empty() => valid = false,
+            _ => match parse_param(sexp, errs) {
+                Some(param) => params.fixed.push(param),
+                None => valid = false,
+            },
+        }
+    }
+    if let Some(rest) = rest {
+        params.rest = parse_param(rest, errs);
+        valid &= params.rest.is_some();
+    }
+    (params, valid)
+}
+
+/// Parses a function definition after checking its name, or None if it is too malformed
+/// to have one
+fn parse_named_defn(s: &Sexp, errs: &mut Vec<CompileError>) -> Option<Function> {
+    let (fname, fspan) = match parse_defn_name(s) {
+        Ok(name) => name,
+        Err(err) => { errs.push(err); return None; },
     };
+    if !is_valid_identifier(&fname) {
+        errs.push(CompileError::InvalidIdentifier(fname, fspan));
+    } else if (*RESERVED).contains(&fname) {
+        errs.push(CompileError::KeywordMisuse(fname, fspan));
+    }
+    parse_defn(s, errs)
+}
+
+/// Parses a list of s-expressions into a list of Snek abstract expressions
+fn parse_exprs(s_exprs: &[Sexp], errs: &mut Vec<CompileError>) -> Vec<Expr> {
+    s_exprs.iter().map(|e| parse_expr(e, errs)).collect()
+}
+
+/// Parses an s-expression into a boxed Snek abstract expression
+fn parse_boxed(s: &Sexp, errs: &mut Vec<CompileError>) -> Box<Expr> {
+    Box::new(parse_expr(s, errs))
+}
 
-    // Return function structure
-    Function { name: fname, args: fargs, body: fbody }
+/// Parse from s-expression into a Snek abstract expression format. A malformed
+/// expression is reported in errs and parsed as an ExprKind::Error node.
+pub fn parse_expr(s: &Sexp, errs: &mut Vec<CompileError>) -> Expr {
+    let span = s.span();
+    match parse_expr_kind(s, errs) {
+        Ok(kind) => Expr { kind, span },
+        Err(err) => {
+            errs.push(err);
+            Expr { kind: ExprKind::Error, span }
+        },
+    }
 }
 
-/// Parse from s-expression into a Snek abstract expression format
-pub fn parse_expr(s: &Sexp, fmap: &HashSet<String>) -> Expr {
+/// Parse the expression kind of an s-expression (the span is attached by parse_expr).
+/// Errors in sub-expressions are reported in errs without failing this expression.
+fn parse_expr_kind(s: &Sexp, errs: &mut Vec<CompileError>) -> Result<ExprKind, CompileError> {
+    let span = s.span();
     match s {
         // Match the single value s-exp (no parentheses)
-        Sexp::Atom(val) => {
+        Sexp::Atom(val, _) => {
             match val {
                 // If number
                 I(n) => {
-                    let n = i64::try_from(*n).unwrap();
-                    if n > LIM - 1 || n < -1 * LIM {
-                        panic!("Invalid")
+                    if !(-LIM..LIM).contains(n) {
+                        Err(CompileError::NumberOutOfRange(n.to_string(), span))
                     } else {
-                        Expr::Number(n)
+                        Ok(ExprKind::Number(*n))
                     }
                 },
+                // If string literal
+                Str(v) => Ok(ExprKind::Str(v.to_string())),
                 // If boolean string
-                S(v) if v == "true" => Expr::Boolean(true),
-                S(v) if v == "false" => Expr::Boolean(false),
+                S(v) if v == "true" => Ok(ExprKind::Boolean(true)),
+                S(v) if v == "false" => Ok(ExprKind::Boolean(false)),
                 // If non-boolean string (identifier)
                 S(v) => {
-                    if !is_valid_identifier(v) {
-                        panic!("Invalid identifier naming conventions");
-                    }
                     if (*RESERVED).contains(v) && v != "input" {
-                        panic!("Invalid identifier - keyword");
+                        return Err(CompileError::KeywordMisuse(v.to_string(), span));
                     }
-                    Expr::Id(v.to_string())
+                    if !is_valid_identifier(v) {
+                        return Err(CompileError::InvalidIdentifier(v.to_string(), span));
+                    }
+                    Ok(ExprKind::Id(v.to_string()))
                 }
-                _ => panic!("Invalid"),
             }
         },
         // Match the list s-exp (has outer parentheses)
-        Sexp::List(vec) => {
+        Sexp::List(vec, _) => {
             match &vec[..] {
                 // Match loop
-                [Sexp::Atom(S(loop_word)), e] if loop_word == "loop" => {
-                    Expr::Loop(Box::new(parse_expr(e, fmap)))
+                [Sexp::Atom(S(loop_word), _), rest @ ..] if loop_word == "loop" => {
+                    match rest {
+                        [e] => Ok(ExprKind::Loop(parse_boxed(e, errs))),
+                        _ => Err(CompileError::InvalidForm(loop_word.to_string(), span)),
+                    }
                 },
                 // Match break
-                [Sexp::Atom(S(break_word)), e] if break_word == "break" => {
-                    Expr::Break(Box::new(parse_expr(e, fmap)))
-                },
-                // Match tuple value
-                [Sexp::Atom(S(tuple_word)), s_exprs @ ..] if tuple_word == "tuple" => {
-                    let mut exprs = Vec::new();
-                    for e in s_exprs {
-                        exprs.push(parse_expr(e, fmap));
+                [Sexp::Atom(S(break_word), _), rest @ ..] if break_word == "break" => {
+                    match rest {
+                        [e] => Ok(ExprKind::Break(parse_boxed(e, errs))),
+                        _ => Err(CompileError::InvalidForm(break_word.to_string(), span)),
                     }
-                    Expr::Tuple(exprs)
                 },
-                // Match block
-                [Sexp::Atom(S(block_word)), s_exprs @ ..] if block_word == "block" => {
-                    if s_exprs.is_empty() {
-                        panic!("Invalid");
-                    } else {
-                        let mut exprs = Vec::new();
-                        for e in s_exprs {
-                            exprs.push(parse_expr(e, fmap));
-                        }
-                        Expr::Block(exprs)
+                // Match raise of any value
+                [Sexp::Atom(S(raise_word), _), rest @ ..] if raise_word == "raise" => {
+                    match rest {
+                        [e] => Ok(ExprKind::Raise(parse_boxed(e, errs))),
+                        _ => Err(CompileError::InvalidForm(raise_word.to_string(), span)),
                     }
                 },
-                // Match function call with arguments
-                [Sexp::Atom(S(func)), s_exprs @ ..] if fmap.contains(func) => {
-                    let mut exprs = Vec::new();
-                    for e in s_exprs {
-                        exprs.push(parse_expr(e, fmap));
+                // Match assertions, with a default message when none is given
+                [Sexp::Atom(S(assert_word), _), rest @ ..] if assert_word == "assert" => {
+                    let msg = match rest {
+                        [_] => Expr { kind: ExprKind::Str(String::from("assertion failed")), span },
+                        [_, msg] => parse_expr(msg, errs),
+                        _ => return Err(CompileError::InvalidForm(assert_word.to_string(), span)),
+                    };
+                    Ok(ExprKind::Assert(parse_boxed(&rest[0], errs), Box::new(msg), sexp_text(s)))
+                },
+                // Match panic with its message
+                [Sexp::Atom(S(panic_word), _), rest @ ..] if panic_word == "panic" => {
+                    match rest {
+                        [msg] => Ok(ExprKind::Panic(parse_boxed(msg, errs), sexp_text(s))),
+                        _ => Err(CompileError::InvalidForm(panic_word.to_string(), span)),
                     }
-                    Expr::Call(func.clone(), exprs)
                 },
-                // Match unary operations
-                [Sexp::Atom(S(op)), e] => {
-                    let op = match Some(&*op.to_string()) {
-                        Some("add1") => Op1::Add1,
-                        Some("sub1") => Op1::Sub1,
-                        Some("isnum") => Op1::IsNum,
-                        Some("isbool") => Op1::IsBool,
-                        Some("print") => Op1::Print,
-                        _ => panic!("Invalid"),
+                // Match try with the catch clause naming the caught value
+                [Sexp::Atom(S(try_word), _), rest @ ..] if try_word == "try" => {
+                    let [body, Sexp::List(clause, _)] = rest else {
+                        return Err(CompileError::InvalidForm(try_word.to_string(), span));
+                    };
+                    let [Sexp::Atom(S(catch_word), _), Sexp::Atom(S(var), var_span), handler] = &clause[..] else {
+                        return Err(CompileError::InvalidForm(try_word.to_string(), span));
                     };
-                    Expr::UnOp(op, Box::new(parse_expr(e, fmap)))
+                    if catch_word != "catch" {
+                        return Err(CompileError::InvalidForm(try_word.to_string(), span));
+                    }
+                    if (*RESERVED).contains(var) {
+                        errs.push(CompileError::KeywordMisuse(var.to_string(), *var_span));
+                    } else if !is_valid_identifier(var) {
+                        errs.push(CompileError::InvalidIdentifier(var.to_string(), *var_span));
+                    }
+                    let binder = Binder { name: var.to_string(), span: *var_span };
+                    Ok(ExprKind::Try(parse_boxed(body, errs), binder, parse_boxed(handler, errs)))
+                },
+                // Match tuple value
+                [Sexp::Atom(S(tuple_word), _), s_exprs @ ..] if tuple_word == "tuple" => {
+                    Ok(ExprKind::Tuple(parse_exprs(s_exprs, errs)))
+                },
+                // Match block
+                [Sexp::Atom(S(block_word), _), s_exprs @ ..] if block_word == "block" => {
+                    if s_exprs.is_empty() {
+                        Err(CompileError::InvalidForm(block_word.to_string(), span))
+                    } else {
+                        Ok(ExprKind::Block(parse_exprs(s_exprs, errs)))
+                    }
                 },
                 // Match let bindings
-                [Sexp::Atom(S(let_word)), Sexp::List(bindings), e] if let_word == "let" => {
-                    let mut binds : Vec<(String, Expr)> = Vec::new();
-                    let mut ids : HashSet<String> = HashSet::new();
-                    
-                    // loop through the bindings and add them to a list (and hashset to check duplicates)
+                [Sexp::Atom(S(let_word), _), rest @ ..] if let_word == "let" => {
+                    let (bindings, e) = match rest {
+                        [Sexp::List(bindings, _), e] if !bindings.is_empty() => (bindings, e),
+                        _ => return Err(CompileError::InvalidForm(let_word.to_string(), span)),
+                    };
+                    let mut binds : Vec<(Binder, Expr)> = Vec::new();
+
+                    // loop through the bindings and add them to a list
                     for b in bindings {
-                        let (id, expr) = parse_bind(b, fmap);
-                        // if any duplicate bindings, error out
-                        if !is_valid_identifier(&id) {
-                            panic!("Invalid identifier naming conventions");
+                        let (id, expr) = match parse_bind(b, errs) {
+                            Ok(bind) => bind,
+                            Err(err) => { errs.push(err); continue; },
+                        };
+                        // report any invalid bindings
+                        if (*RESERVED).contains(&id.name) {
+                            errs.push(CompileError::KeywordMisuse(id.name.clone(), id.span));
+                        } else if !is_valid_identifier(&id.name) {
+                            errs.push(CompileError::InvalidIdentifier(id.name.clone(), id.span));
                         }
-                        if ids.contains(&id) {
-                            panic!("Duplicate binding");
-                        }
-                        if (*RESERVED).contains(&id) {
-                            panic!("Invalid identifier - keyword");
-                        }
-                        ids.insert(id.clone());
                         binds.push((id, expr));
                     }
-                    // cannot have no bindings in the let
-                    if binds.len() == 0 {
-                        panic!("Invalid");
+                    Ok(ExprKind::Let(binds, parse_boxed(e, errs)))
+                },
+                // Match letrec with its group of local function definitions
+                [Sexp::Atom(S(letrec_word), _), rest @ ..] if letrec_word == "letrec" => {
+                    match rest {
+                        [Sexp::List(defns, _), e] if !defns.is_empty() && defns.iter().all(is_fun_defn) => {
+                            let funcs = defns.iter().filter_map(|defn| parse_named_defn(defn, errs)).collect();
+                            Ok(ExprKind::LetRec(funcs, parse_boxed(e, errs)))
+                        },
+                        _ => Err(CompileError::InvalidForm(letrec_word.to_string(), span)),
                     }
-                    Expr::Let(binds, Box::new(parse_expr(e, fmap)))
                 },
                 // Match if clause
-                [Sexp::Atom(S(if_word)), e1, e2, e3] if if_word == "if" => {
-                    Expr::If(Box::new(parse_expr(e1, fmap)), 
-                        Box::new(parse_expr(e2, fmap)), 
-                        Box::new(parse_expr(e3, fmap)))
+                [Sexp::Atom(S(if_word), _), rest @ ..] if if_word == "if" => {
+                    match rest {
+                        [e1, e2, e3] => Ok(ExprKind::If(parse_boxed(e1, errs),
+                            parse_boxed(e2, errs),
+                            parse_boxed(e3, errs))),
+                        _ => Err(CompileError::InvalidForm(if_word.to_string(), span)),
+                    }
+                },
+                // Match short-circuit and/or
+                [Sexp::Atom(S(word), _), rest @ ..] if word == "and" || word == "or" => {
+                    match rest {
+                        [e1, e2] if word == "and" => Ok(ExprKind::And(parse_boxed(e1, errs), parse_boxed(e2, errs))),
+                        [e1, e2] => Ok(ExprKind::Or(parse_boxed(e1, errs), parse_boxed(e2, errs))),
+                        _ => Err(CompileError::WrongArity { name: word.to_string(), expected: Arity::Exactly(2), found: rest.len(), span }),
+                    }
+                },
+                // Match cond clauses, ending with an else clause
+                [Sexp::Atom(S(cond_word), _), rest @ ..] if cond_word == "cond" => {
+                    let Some((Sexp::List(else_clause, _), clauses)) = rest.split_last() else {
+                        return Err(CompileError::InvalidForm(cond_word.to_string(), span));
+                    };
+                    let [Sexp::Atom(S(else_word), _), e_else] = &else_clause[..] else {
+                        return Err(CompileError::InvalidForm(cond_word.to_string(), span));
+                    };
+                    if else_word != "else" {
+                        return Err(CompileError::InvalidForm(cond_word.to_string(), span));
+                    }
+                    let mut rclauses : Vec<(Expr, Expr)> = Vec::new();
+                    for clause in clauses {
+                        match clause {
+                            Sexp::List(vec, _) if vec.len() == 2 => rclauses.push((parse_expr(&vec[0], errs), parse_expr(&vec[1], errs))),
+                            _ => return Err(CompileError::InvalidForm(cond_word.to_string(), span)),
+                        }
+                    }
+                    Ok(ExprKind::Cond(rclauses, parse_boxed(e_else, errs)))
                 },
                 // Match set! for identifiers
-                [Sexp::Atom(S(set_word)), Sexp::Atom(S(var)), e] if set_word == "set!" => {
-                    if !is_valid_identifier(var) {
-                        panic!("Invalid identifier naming conventions");
+                [Sexp::Atom(S(set_word), _), rest @ ..] if set_word == "set!" => {
+                    match rest {
+                        [Sexp::Atom(S(var), var_span), e] => {
+                            if (*RESERVED).contains(var) {
+                                return Err(CompileError::KeywordMisuse(var.to_string(), *var_span));
+                            }
+                            if !is_valid_identifier(var) {
+                                return Err(CompileError::InvalidIdentifier(var.to_string(), *var_span));
+                            }
+                            Ok(ExprKind::Set(Binder { name: var.to_string(), span: *var_span }, parse_boxed(e, errs)))
+                        },
+                        _ => Err(CompileError::InvalidForm(set_word.to_string(), span)),
                     }
-                    if (*RESERVED).contains(var) {
-                        panic!("Invalid identifier - keyword");
+                },
+                // Match lambda for anonymous functions
+                [Sexp::Atom(S(lambda_word), _), rest @ ..] if lambda_word == "lambda" => {
+                    match rest {
+                        [Sexp::List(params, _), body] => match parse_params(params, errs) {
+                            (params, true) => Ok(ExprKind::Lambda(params, parse_boxed(body, errs))),
+                            (_, false) => Err(CompileError::InvalidForm(lambda_word.to_string(), span)),
+                        },
+                        _ => Err(CompileError::InvalidForm(lambda_word.to_string(), span)),
                     }
-                    Expr::Set(var.to_string(), Box::new(parse_expr(e, fmap)))
+                },
+                // Match apply of a function value to the elements of a tuple
+                [Sexp::Atom(S(apply_word), _), rest @ ..] if apply_word == "apply" => {
+                    match rest {
+                        [e_func, e_tuple] => Ok(ExprKind::ApplyTuple(parse_boxed(e_func, errs), parse_boxed(e_tuple, errs))),
+                        _ => Err(CompileError::WrongArity { name: apply_word.to_string(), expected: Arity::Exactly(2), found: rest.len(), span }),
+                    }
+                },
+                // Match arg and argc for the command-line arguments
+                [Sexp::Atom(S(arg_word), _), rest @ ..] if arg_word == "arg" => {
+                    match rest {
+                        [e_index] => Ok(ExprKind::Arg(parse_boxed(e_index, errs))),
+                        _ => Err(CompileError::WrongArity { name: arg_word.to_string(), expected: Arity::Exactly(1), found: rest.len(), span }),
+                    }
+                },
+                [Sexp::Atom(S(argc_word), _), rest @ ..] if argc_word == "argc" => {
+                    match rest {
+                        [] => Ok(ExprKind::ArgCount),
+                        _ => Err(CompileError::WrongArity { name: argc_word.to_string(), expected: Arity::Exactly(0), found: rest.len(), span }),
+                    }
+                },
+                // Match match with its cases, tried in order
+                [Sexp::Atom(S(match_word), _), rest @ ..] if match_word == "match" => {
+                    let (e, cases) = match rest {
+                        [e, cases @ ..] if !cases.is_empty() => (e, cases),
+                        _ => return Err(CompileError::InvalidForm(match_word.to_string(), span)),
+                    };
+                    let e = parse_boxed(e, errs);
+                    let mut rcases : Vec<(Pattern, Expr)> = Vec::new();
+                    for case in cases {
+                        let Sexp::List(case_vec, _) = case else {
+                            return Err(CompileError::InvalidForm(match_word.to_string(), span));
+                        };
+                        let [pattern, body] = &case_vec[..] else {
+                            return Err(CompileError::InvalidForm(match_word.to_string(), span));
+                        };
+                        rcases.push((parse_pattern(pattern, errs), parse_expr(body, errs)));
+                    }
+                    Ok(ExprKind::Match(e, rcases))
                 },
                 // Match tinit for initializing a tuple
-                [Sexp::Atom(S(tinit_word)), e_length, e_value] if tinit_word == "tinit" => {
-                    Expr::TInit(Box::new(parse_expr(e_length, fmap)),
-                            Box::new(parse_expr(e_value,fmap)))
+                [Sexp::Atom(S(tinit_word), _), rest @ ..] if tinit_word == "tinit" => {
+                    match rest {
+                        [e_length, e_value] => Ok(ExprKind::TInit(parse_boxed(e_length, errs),
+                            parse_boxed(e_value, errs))),
+                        _ => Err(CompileError::WrongArity { name: tinit_word.to_string(), expected: Arity::Exactly(2), found: rest.len(), span }),
+                    }
                 },
                 // Match tset for tuple elements
-                [Sexp::Atom(S(tset_word)), e_tuple, e_index, e_value] if tset_word == "tset" => {
-                    Expr::TSet(Box::new(parse_expr(e_tuple, fmap)), 
-                            Box::new(parse_expr(e_index, fmap)),
-                            Box::new(parse_expr(e_value,fmap)))
+                [Sexp::Atom(S(tset_word), _), rest @ ..] if tset_word == "tset" => {
+                    match rest {
+                        [e_tuple, e_index, e_value] => Ok(ExprKind::TSet(parse_boxed(e_tuple, errs),
+                            parse_boxed(e_index, errs),
+                            parse_boxed(e_value, errs))),
+                        _ => Err(CompileError::WrongArity { name: tset_word.to_string(), expected: Arity::Exactly(3), found: rest.len(), span }),
+                    }
                 },
                 // Match tget for tuple elements
-                [Sexp::Atom(S(tget_word)), e_tuple, e_index] if tget_word == "tget" => {
-                    Expr::TGet(Box::new(parse_expr(e_tuple, fmap)), 
-                            Box::new(parse_expr(e_index, fmap)))
+                [Sexp::Atom(S(tget_word), _), rest @ ..] if tget_word == "tget" => {
+                    match rest {
+                        [e_tuple, e_index] => Ok(ExprKind::TGet(parse_boxed(e_tuple, errs),
+                            parse_boxed(e_index, errs))),
+                        _ => Err(CompileError::WrongArity { name: tget_word.to_string(), expected: Arity::Exactly(2), found: rest.len(), span }),
+                    }
                 },
-                // Match binary operations
-                [Sexp::Atom(S(op)), e1, e2] => {
-                    let op = match Some(&*op.to_string()) {
-                        Some("+") => Op2::Plus,
-                        Some("-") => Op2::Minus,
-                        Some("*") => Op2::Times,
-                        Some("<") => Op2::Lt,
-                        Some(">") => Op2::Gt,
-                        Some("<=") => Op2::Lte,
-                        Some(">=") => Op2::Gte,
-                        Some("=") => Op2::Equal,
-                        _ => panic!("Invalid"),
-                    };
-                    Expr::BinOp(op, Box::new(parse_expr(e1, fmap)), Box::new(parse_expr(e2, fmap)))
+                // Match tslice for slicing a tuple
+                [Sexp::Atom(S(tslice_word), _), rest @ ..] if tslice_word == "tslice" => {
+                    match rest {
+                        [e_tuple, e_start, e_end] => Ok(ExprKind::TSlice(parse_boxed(e_tuple, errs),
+                            parse_boxed(e_start, errs),
+                            parse_boxed(e_end, errs))),
+                        _ => Err(CompileError::WrongArity { name: tslice_word.to_string(), expected: Arity::Exactly(3), found: rest.len(), span }),
+                    }
                 },
-                _ => panic!("Invalid"),
+                // Match substring for slicing a string
+                [Sexp::Atom(S(substring_word), _), rest @ ..] if substring_word == "substring" => {
+                    match rest {
+                        [e_string, e_start, e_end] => Ok(ExprKind::Substring(parse_boxed(e_string, errs),
+                            parse_boxed(e_start, errs),
+                            parse_boxed(e_end, errs))),
+                        _ => Err(CompileError::WrongArity { name: substring_word.to_string(), expected: Arity::Exactly(3), found: rest.len(), span }),
+                    }
+                },
+                // Match unary and binary operations
+                [Sexp::Atom(S(op), op_span), rest @ ..] => {
+                    if let Some(op1) = parse_op1(op) {
+                        return match rest {
+                            [e] => Ok(ExprKind::UnOp(op1, parse_boxed(e, errs))),
+                            _ => Err(CompileError::WrongArity { name: op.to_string(), expected: Arity::Exactly(1), found: rest.len(), span }),
+                        };
+                    }
+                    if let Some(op2) = parse_op2(op) {
+                        return match rest {
+                            [e1, e2] => Ok(ExprKind::BinOp(op2, parse_boxed(e1, errs), parse_boxed(e2, errs))),
+                            _ => Err(CompileError::WrongArity { name: op.to_string(), expected: Arity::Exactly(2), found: rest.len(), span }),
+                        };
+                    }
+                    if (*RESERVED).contains(op) {
+                        Err(CompileError::InvalidForm(op.to_string(), span))
+                    } else {
+                        // Calls are resolved to function definitions or function values later, which
+                        // also reports the names that cannot be called
+                        Ok(ExprKind::Call(Binder { name: op.to_string(), span: *op_span }, parse_exprs(rest, errs)))
+                    }
+                },
+                [Sexp::Atom(I(n), op_span), ..] => Err(CompileError::UnknownOperator(n.to_string(), *op_span)),
+                [Sexp::Atom(Str(v), op_span), ..] => Err(CompileError::UnknownOperator(format!("{v:?}"), *op_span)),
+                // Calls to the function value of a nested expression
+                [head @ Sexp::List(..), rest @ ..] => Ok(ExprKind::Apply(parse_boxed(head, errs), parse_exprs(rest, errs))),
+                [] => Err(CompileError::InvalidForm(String::from("()"), span)),
+            }
+        },
+    }
+}
+
+/// Parses a unary operator name
+fn parse_op1(op: &str) -> Option<Op1> {
+    match op {
+        "add1" => Some(Op1::Add1),
+        "sub1" => Some(Op1::Sub1),
+        "isnum" => Some(Op1::IsNum),
+        "isbool" => Some(Op1::IsBool),
+        "print" => Some(Op1::Print),
+        "string-length" => Some(Op1::StringLength),
+        "number->string" => Some(Op1::NumberToString),
+        "string->number" => Some(Op1::StringToNumber),
+        "not" => Some(Op1::Not),
+        "bitnot" => Some(Op1::BitNot),
+        "tlen" => Some(Op1::TupleLength),
+        "istuple" => Some(Op1::IsTuple),
+        "tcopy" => Some(Op1::TupleCopy),
+        _ => None,
+    }
+}
+
+/// Parses a binary operator name
+fn parse_op2(op: &str) -> Option<Op2> {
+    match op {
+        "+" => Some(Op2::Plus),
+        "-" => Some(Op2::Minus),
+        "*" => Some(Op2::Times),
+        "/" => Some(Op2::Divide),
+        "quotient" => Some(Op2::Quotient),
+        "remainder" => Some(Op2::Remainder),
+        "modulo" => Some(Op2::Modulo),
+        "bitand" => Some(Op2::BitAnd),
+        "bitor" => Some(Op2::BitOr),
+        "bitxor" => Some(Op2::BitXor),
+        "shift-left" => Some(Op2::ShiftLeft),
+        "shift-right" => Some(Op2::ShiftRight),
+        "<" => Some(Op2::Lt),
+        ">" => Some(Op2::Gt),
+        "<=" => Some(Op2::Lte),
+        ">=" => Some(Op2::Gte),
+        "=" => Some(Op2::Equal),
+        "equal?" => Some(Op2::StructuralEqual),
+        "string-append" => Some(Op2::StringAppend),
+        "string=?" => Some(Op2::StringEqual),
+        "tappend" => Some(Op2::TupleAppend),
+        _ => None,
+    }
+}
+
+/// Parse a match pattern. An invalid pattern is reported in errs and parsed as a wildcard.
+fn parse_pattern(s: &Sexp, errs: &mut Vec<CompileError>) -> Pattern {
+    match s {
+        Sexp::Atom(I(n), span) => {
+            if !(-LIM..LIM).contains(n) {
+                errs.push(CompileError::NumberOutOfRange(n.to_string(), *span));
             }
+            Pattern::Number(*n)
+        },
+        Sexp::Atom(S(v), _) if v == "_" => Pattern::Wildcard,
+        Sexp::Atom(S(v), _) if v == "true" => Pattern::Boolean(true),
+        Sexp::Atom(S(v), _) if v == "false" => Pattern::Boolean(false),
+        Sexp::Atom(S(v), span) => {
+            if (*RESERVED).contains(v) {
+                errs.push(CompileError::KeywordMisuse(v.to_string(), *span));
+            } else if !is_valid_identifier(v) {
+                errs.push(CompileError::InvalidIdentifier(v.to_string(), *span));
+            }
+            Pattern::Var(Binder { name: v.to_string(), span: *span })
+        },
+        Sexp::List(vec, _) => match &vec[..] {
+            [Sexp::Atom(S(tuple_word), _), elems @ ..] if tuple_word == "tuple" => {
+                Pattern::Tuple(elems.iter().map(|elem| parse_pattern(elem, errs)).collect())
+            },
+            _ => {
+                errs.push(CompileError::InvalidPattern(s.span()));
+                Pattern::Wildcard
+            },
+        },
+        Sexp::Atom(Str(_), span) => {
+            errs.push(CompileError::InvalidPattern(*span));
+            Pattern::Wildcard
         },
     }
 }
 
+/// Source text of an s-expression, with its atoms separated by single spaces, as reported by
+/// failed assertions and panics
+fn sexp_text(s: &Sexp) -> String {
+    match s {
+        Sexp::Atom(I(n), _) => n.to_string(),
+        Sexp::Atom(S(word), _) => word.to_string(),
+        Sexp::Atom(Str(lit), _) => format!("{lit:?}"),
+        Sexp::List(vec, _) => format!("({})", vec.iter().map(sexp_text).collect::<Vec<_>>().join(" ")),
+    }
+}
+
 /// Parse a singular let binding by recursion
-fn parse_bind(s: &Sexp, fmap: &HashSet<String>) -> (String, Expr) {
+fn parse_bind(s: &Sexp, errs: &mut Vec<CompileError>) -> Result<(Binder, Expr), CompileError> {
     // Match let binding list s-exp
-    if let Sexp::List(vec) = s {
-        if let [Sexp::Atom(S(var)), e] = &vec[..] {
-            // Return identifier string and parsed inner expression
-            (var.to_string(), parse_expr(e, fmap)) 
-        } else {
-            panic!("Invalid");
+    if let Sexp::List(vec, _) = s {
+        if let [Sexp::Atom(S(var), span), e] = &vec[..] {
+            // Return identifier and parsed inner expression
+            return Ok((Binder { name: var.to_string(), span: *span }, parse_expr(e, errs)));
         }
-    } else {
-        panic!("Invalid");
     }
-}
\ No newline at end of file
+    Err(CompileError::InvalidForm(String::from("let"), s.span()))
+}
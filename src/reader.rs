@@ -0,0 +1,208 @@
+/*
+    reader.rs
+
+    Reads Snek source text into located s-expressions, recording the span of every atom and list.
+    String literals are read as single atoms, with their escape sequences processed.
+*/
+
+use crate::types::*;
+
+/// Cursor over the source text that tracks the current line and column
+struct Reader<'a> {
+    text : &'a str,
+    pos : usize,    // byte offset of the next character
+    line : usize,   // 1-based line of the next character
+    col : usize,    // 1-based column of the next character
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a reader at the start of the source text
+    fn new(text: &'a str) -> Reader<'a> {
+        Reader { text, pos: 0, line: 1, col: 1 }
+    }
+
+    /// Peeks at the next character without consuming it
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    /// Consumes the next character, advancing the line and column
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+
+    /// Empty span at the current position
+    fn here(&self) -> Span {
+        Span { start: self.pos, end: self.pos, line: self.line, col: self.col }
+    }
+
+    /// Span from an earlier starting position up to the current position
+    fn since(&self, start: Span) -> Span {
+        Span { end: self.pos, ..start }
+    }
+
+    /// Returns whether the remaining text starts with the given prefix
+    fn at(&self, prefix: &str) -> bool {
+        self.text[self.pos..].starts_with(prefix)
+    }
+
+    /// Skips whitespace and comments between s-expressions: `;` line comments,
+    /// nestable `#| ... |#` block comments and `#;` datum comments
+    fn skip_space(&mut self) -> Result<(), CompileError> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => { self.bump(); },
+                Some(';') => {
+                    while !matches!(self.bump(), Some('\n') | None) {}
+                },
+                Some('#') if self.at("#|") => self.skip_block_comment()?,
+                Some('#') if self.at("#;") => {
+                    // Read and discard the next datum
+                    let start = self.here();
+                    self.bump();
+                    self.bump();
+                    self.skip_space()?;
+                    if matches!(self.peek(), Some(')') | None) {
+                        return Err(CompileError::InvalidSexp(String::from("datum comment '#;' without a datum"),
+                            Span { end: start.start + 2, ..start }));
+                    }
+                    self.read_sexp()?;
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Skips a block comment starting at the current position, including nested block comments
+    fn skip_block_comment(&mut self) -> Result<(), CompileError> {
+        let start = self.here();
+        let mut depth = 0;
+        loop {
+            if self.at("#|") {
+                depth += 1;
+                self.bump();
+                self.bump();
+            } else if self.at("|#") {
+                depth -= 1;
+                self.bump();
+                self.bump();
+                if depth == 0 {
+                    return Ok(());
+                }
+            } else if self.bump().is_none() {
+                return Err(CompileError::InvalidSexp(String::from("unterminated block comment"),
+                    Span { end: start.start + 2, ..start }));
+            }
+        }
+    }
+
+    /// Reads a single s-expression starting at the current position
+    fn read_sexp(&mut self) -> Result<Sexp, CompileError> {
+        let start = self.here();
+        match self.peek() {
+            Some('(') => {
+                self.bump();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_space()?;
+                    match self.peek() {
+                        Some(')') => {
+                            self.bump();
+                            return Ok(Sexp::List(items, self.since(start)));
+                        },
+                        None => {
+                            return Err(CompileError::InvalidSexp(String::from("unclosed '('"),
+                                Span { end: start.start + 1, ..start }));
+                        },
+                        Some(_) => items.push(self.read_sexp()?),
+                    }
+                }
+            },
+            Some(')') => {
+                self.bump();
+                Err(CompileError::InvalidSexp(String::from("unexpected ')'"), self.since(start)))
+            },
+            Some('"') => self.read_string(),
+            Some(_) => self.read_atom(),
+            None => Err(CompileError::InvalidSexp(String::from("unexpected end of file"), start)),
+        }
+    }
+
+    /// Reads a string literal delimited by double quotes. The escape sequences \n, \t, \r, \0,
+    /// \\ and \" stand for a line feed, tab, carriage return, NUL, backslash and double quote.
+    fn read_string(&mut self) -> Result<Sexp, CompileError> {
+        let start = self.here();
+        self.bump();
+        let mut contents = String::new();
+        loop {
+            let escape = self.here();
+            match self.bump() {
+                Some('"') => return Ok(Sexp::Atom(Atom::Str(contents), self.since(start))),
+                Some('\\') => {
+                    contents.push(match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some('\\') => '\\',
+                        Some('"') => '"',
+                        Some(c) => {
+                            return Err(CompileError::InvalidSexp(format!("unknown escape sequence '\\{c}' in string literal"),
+                                self.since(escape)));
+                        },
+                        None => break,
+                    });
+                },
+                Some(c) => contents.push(c),
+                None => break,
+            }
+        }
+        Err(CompileError::InvalidSexp(String::from("unterminated string literal"), Span { end: start.start + 1, ..start }))
+    }
+
+    /// Reads an atom: everything up to the next whitespace, parenthesis, string or line comment
+    fn read_atom(&mut self) -> Result<Sexp, CompileError> {
+        let start = self.here();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == '(' || c == ')' || c == ';' || c == '"' {
+                break;
+            }
+            self.bump();
+        }
+        let span = self.since(start);
+        let token = &self.text[span.start..span.end];
+
+        // Integer literals are an optional minus sign followed by digits
+        let digits = token.strip_prefix('-').unwrap_or(token);
+        if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+            return match token.parse::<i64>() {
+                Ok(n) => Ok(Sexp::Atom(Atom::I(n), span)),
+                Err(_) => Err(CompileError::NumberOutOfRange(token.to_string(), span)),
+            };
+        }
+        Ok(Sexp::Atom(Atom::S(token.to_string()), span))
+    }
+}
+
+/// Reads the whole source text into the list of top-level s-expressions
+pub fn read_program(text: &str) -> Result<(Vec<Sexp>, Span), CompileError> {
+    let mut reader = Reader::new(text);
+    let start = reader.here();
+    let mut sexps = Vec::new();
+    loop {
+        reader.skip_space()?;
+        if reader.peek().is_none() {
+            break;
+        }
+        sexps.push(reader.read_sexp()?);
+    }
+    Ok((sexps, reader.since(start)))
+}
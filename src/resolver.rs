@@ -0,0 +1,468 @@
+/*
+    resolver.rs
+
+    Resolves a parsed program into the resolved abstract syntax, binding every identifier to its
+    variable binding and every call to its function definition. Checks all scoping rules (duplicate
+    names, bound identifiers, known functions and their arity, break placement), collecting
+    every violation instead of stopping at the first, so code generation can assume a well-formed program.
+    Lambda expressions and local functions are lifted out of their enclosing expression, recording the
+    variables they capture, and calls of the operations generated by struct declarations become
+    dedicated record expressions.
+*/
+
+use crate::types::*;
+use crate::utils::{is_struct_op_name, is_valid_identifier};
+
+use im::HashMap;
+
+/// Context of an expression for name resolution
+#[derive(Copy, Clone)]
+struct ResolveContext<'a> {
+    env : &'a HashMap<String, BindId>,  // variables in scope
+    globals : usize,                    // number of globals initialized before the expression runs
+    in_loop : bool,                     // whether inside a loop or not
+}
+
+/// Lambda whose body is being resolved
+struct LambdaFrame {
+    start : BindId,             // first binding made inside the lambda (its first parameter)
+    captures : Vec<BindId>,     // bindings made outside the lambda and used inside it
+}
+
+/// Operation generated by a struct declaration
+#[derive(Copy, Clone)]
+enum StructOp {
+    New(StructId),
+    Is(StructId),
+    Get(StructId, usize),
+    Set(StructId, usize),
+}
+
+impl StructOp {
+    /// Number of arguments the operation takes, given the number of fields of its struct
+    fn arity(&self, nfields: usize) -> usize {
+        match self {
+            StructOp::New(_) => nfields,
+            StructOp::Is(_) | StructOp::Get(..) => 1,
+            StructOp::Set(..) => 2,
+        }
+    }
+}
+
+/// State shared across the whole program during resolution
+struct Resolver<'a> {
+    funcs : HashMap<String, (FuncId, Arity, usize)>,    // function name to id, arity and number of parameters before the rest one
+    globals : HashMap<String, GlobalId>,        // global name to id map
+    struct_ops : HashMap<String, StructOp>,     // generated struct operation name to operation map
+    nfields : Vec<usize>,                       // number of fields of every struct, indexed by StructId
+    bindings : Vec<Binding>,                    // every binding found so far, indexed by BindId
+    lambdas : Vec<RLambda>,                     // every lambda resolved so far, indexed by LambdaId
+    strings : Vec<String>,                      // distinct string literals found so far, indexed by StrId
+    frames : Vec<LambdaFrame>,                  // lambdas enclosing the current expression, innermost last
+    assigned : Vec<(BindId, Span)>,             // target of every set! on a variable found so far
+    errs : &'a mut Vec<CompileError>,
+}
+
+/// Resolves every function and the main expression, appending all scope errors to errs.
+/// The resolved program is only meaningful if no errors were found.
+pub fn resolve_program(prog: &Program, errs: &mut Vec<CompileError>) -> RProgram {
+    let mut resolver = Resolver { funcs: HashMap::new(), globals: HashMap::new(), struct_ops: HashMap::new(), nfields: Vec::new(), bindings: Vec::new(), lambdas: Vec::new(), strings: Vec::new(), frames: Vec::new(), assigned: Vec::new(), errs };
+    for (id, func) in prog.defns.iter().enumerate() {
+        // Keep the first definition of a duplicate name so its calls are checked against it
+        if let Some((first, _, _)) = resolver.funcs.get(&func.name) {
+            let first_span = prog.defns[*first].name_span;
+            resolver.errs.push(CompileError::DuplicateFunction(func.name.clone(), func.name_span, first_span));
+        } else {
+            let npositional = func.params.fixed.len() + func.params.optional.len();
+            resolver.funcs.insert(func.name.clone(), (id, func.params.arity(), npositional));
+        }
+    }
+
+    // Every struct generates its constructor, predicate, accessors and mutators, whose names
+    // must not clash with functions or with the operations of other structs
+    let mut op_spans : HashMap<String, Span> = prog.defns.iter().map(|func| (func.name.clone(), func.name_span)).collect();
+    let mut structs : Vec<RStruct> = Vec::new();
+    for (sid, st) in prog.structs.iter().enumerate() {
+        let mut fields : Vec<String> = Vec::new();
+        for (i, field) in st.fields.iter().enumerate() {
+            if let Some(first) = st.fields[..i].iter().find(|prev| prev.name == field.name) {
+                resolver.errs.push(CompileError::DuplicateField(field.name.clone(), field.span, first.span));
+            }
+            fields.push(field.name.clone());
+        }
+        let rstruct = RStruct { name: st.name.clone(), fields };
+        let mut ops = vec![(rstruct.name.clone(), StructOp::New(sid), st.name_span),
+                           (rstruct.predicate(), StructOp::Is(sid), st.name_span)];
+        for (i, field) in st.fields.iter().enumerate() {
+            ops.push((rstruct.accessor(i), StructOp::Get(sid, i), field.span));
+            ops.push((rstruct.mutator(i), StructOp::Set(sid, i), field.span));
+        }
+        for (name, op, span) in ops {
+            if let Some(first_span) = op_spans.get(&name) {
+                // Repeated fields were already reported above
+                if !st.fields.iter().any(|f| f.span == *first_span) {
+                    resolver.errs.push(CompileError::DuplicateFunction(name, span, *first_span));
+                }
+            } else {
+                op_spans.insert(name.clone(), span);
+                resolver.struct_ops.insert(name, op);
+            }
+        }
+        resolver.nfields.push(st.fields.len());
+        structs.push(rstruct);
+    }
+
+    // Globals share the namespace of functions, since both can be called by name
+    for (gid, global) in prog.globals.iter().enumerate() {
+        let first_span = match resolver.globals.get(&global.name) {
+            Some(first) => Some(prog.globals[*first].name_span),
+            None => op_spans.get(&global.name).copied(),
+        };
+        if let Some(first_span) = first_span {
+            resolver.errs.push(CompileError::DuplicateGlobal(global.name.clone(), global.name_span, first_span));
+        } else {
+            resolver.globals.insert(global.name.clone(), gid);
+        }
+    }
+
+    // Function bodies only see their own parameters
+    let mut funcs : Vec<RFunction> = Vec::new();
+    for func in &prog.defns {
+        let mut env : HashMap<String, BindId> = HashMap::new();
+        let ctxt = ResolveContext { env: &HashMap::new(), globals: prog.globals.len(), in_loop: false };
+        let (params, defaults) = resolver.bind_params(&func.params, &mut env, ctxt);
+        let body = resolver.resolve_expr(&func.body, ResolveContext { env: &env, ..ctxt });
+        let variadic = func.params.rest.is_some();
+        funcs.push(RFunction { name: func.name.clone(), name_span: func.name_span, params, defaults, variadic, body });
+    }
+
+    // Each initializer only sees the globals defined before it
+    let mut globals : Vec<RExpr> = Vec::new();
+    for (gid, global) in prog.globals.iter().enumerate() {
+        let ctxt = ResolveContext { env: &HashMap::new(), globals: gid, in_loop: false };
+        globals.push(resolver.resolve_expr(&global.body, ctxt));
+    }
+
+    let ctxt = ResolveContext { env: &HashMap::new(), globals: prog.globals.len(), in_loop: false };
+    let main = resolver.resolve_expr(&prog.main, ctxt);
+
+    // A lambda holds a copy of each variable it captures, which a set! would make stale
+    for (id, span) in &resolver.assigned {
+        if resolver.lambdas.iter().any(|lambda| lambda.captures.contains(id)) {
+            let binding = &resolver.bindings[*id];
+            resolver.errs.push(CompileError::CapturedAssignment(binding.name.clone(), *span, binding.span));
+        }
+    }
+    RProgram { funcs, lambdas: resolver.lambdas, strings: resolver.strings, structs, globals, main, bindings: resolver.bindings }
+}
+
+impl<'a> Resolver<'a> {
+    /// Records a new binding occurrence and returns its id
+    fn bind(&mut self, binder: &Binder, kind: BindKind, shadows: Option<BindId>) -> BindId {
+        self.bindings.push(Binding { name: binder.name.clone(), span: binder.span, kind, shadows });
+        self.bindings.len() - 1
+    }
+
+    /// Returns the global of a name, if it is initialized before the expression of ctxt runs
+    fn global(&self, name: &str, ctxt: ResolveContext) -> Option<GlobalId> {
+        self.globals.get(name).copied().filter(|gid| *gid < ctxt.globals)
+    }
+
+    /// Records a use of a variable, which every enclosing lambda defined inside its scope captures
+    fn capture(&mut self, id: BindId) {
+        for frame in self.frames.iter_mut().filter(|frame| id < frame.start) {
+            if !frame.captures.contains(&id) {
+                frame.captures.push(id);
+            }
+        }
+    }
+
+    /// Resolves a call of an operation generated by a struct declaration, checking its arity
+    fn resolve_struct_op(&mut self, op: StructOp, name: &str, args: Vec<RExpr>, span: Span) -> RExprKind {
+        let sid = match op { StructOp::New(sid) | StructOp::Is(sid) | StructOp::Get(sid, _) | StructOp::Set(sid, _) => sid };
+        let expected = op.arity(self.nfields[sid]);
+        if args.len() != expected {
+            self.errs.push(CompileError::WrongArity { name: name.to_string(), expected: Arity::Exactly(expected), found: args.len(), span });
+            return RExprKind::Error;
+        }
+        let mut args = args.into_iter();
+        match op {
+            StructOp::New(sid) => RExprKind::StructNew(sid, args.collect()),
+            StructOp::Is(sid) => RExprKind::StructIs(sid, Box::new(args.next().unwrap())),
+            StructOp::Get(sid, i) => RExprKind::StructGet(sid, i, Box::new(args.next().unwrap())),
+            StructOp::Set(sid, i) => {
+                let record = args.next().unwrap();
+                RExprKind::StructSet(sid, i, Box::new(record), Box::new(args.next().unwrap()))
+            },
+        }
+    }
+
+    /// Resolves a match pattern, binding its variables in env. A name may only be bound once
+    /// per pattern, and the bindings found so far are kept in bound.
+    fn resolve_pattern(&mut self, pattern: &Pattern, outer: &HashMap<String, BindId>,
+                       env: &mut HashMap<String, BindId>, bound: &mut Vec<Binder>) -> RPattern {
+        match pattern {
+            Pattern::Wildcard => RPattern::Wildcard,
+            Pattern::Number(n) => RPattern::Number(*n),
+            Pattern::Boolean(b) => RPattern::Boolean(*b),
+            Pattern::Var(binder) => {
+                if let Some(first) = bound.iter().find(|prev| prev.name == binder.name) {
+                    self.errs.push(CompileError::DuplicateBinding(binder.name.clone(), binder.span, first.span));
+                }
+                let id = self.bind(binder, BindKind::Local, outer.get(&binder.name).copied());
+                env.insert(binder.name.clone(), id);
+                bound.push(binder.clone());
+                RPattern::Var(id)
+            },
+            Pattern::Tuple(elems) => {
+                RPattern::Tuple(elems.iter().map(|elem| self.resolve_pattern(elem, outer, env, bound)).collect())
+            },
+        }
+    }
+
+    /// Binds the parameters of a function or lambda in env, with the rest parameter last, and
+    /// returns their ids and the resolved defaults of the optional parameters. A default sees the
+    /// parameters before its own. Parameter names must be distinct.
+    fn bind_params(&mut self, params: &Params, env: &mut HashMap<String, BindId>, ctxt: ResolveContext) -> (Vec<BindId>, Vec<RExpr>) {
+        let binders : Vec<&Binder> = params.binders().collect();
+        let mut ids : Vec<BindId> = Vec::new();
+        let mut defaults : Vec<RExpr> = Vec::new();
+        for (i, param) in binders.iter().enumerate() {
+            if let Some(first) = binders[..i].iter().find(|prev| prev.name == param.name) {
+                self.errs.push(CompileError::DuplicateParameter(param.name.clone(), param.span, first.span));
+            }
+            if let Some((_, default)) = i.checked_sub(params.fixed.len()).and_then(|j| params.optional.get(j)) {
+                defaults.push(self.resolve_expr(default, ResolveContext { env, in_loop: false, ..ctxt }));
+            }
+            let id = self.bind(param, BindKind::Param(i), None);
+            env.insert(param.name.clone(), id);
+            ids.push(id);
+        }
+        (ids, defaults)
+    }
+
+    /// Resolves the parameters and body of a lambda or local function, lifting it out of the
+    /// enclosing expression. The body sees the enclosing variables and the parameters, but
+    /// cannot break out of it.
+    fn resolve_lambda(&mut self, params: &Params, body: &Expr, ctxt: ResolveContext) -> LambdaId {
+        let start = self.bindings.len();
+        let mut new_env = ctxt.env.clone();
+        self.frames.push(LambdaFrame { start, captures: Vec::new() });
+        let (ids, defaults) = self.bind_params(params, &mut new_env, ctxt);
+        let body = self.resolve_expr(body, ResolveContext { env: &new_env, in_loop: false, ..ctxt });
+        let captures = self.frames.pop().map(|frame| frame.captures).unwrap_or_default();
+        self.lambdas.push(RLambda { params: ids, defaults, variadic: params.rest.is_some(), captures, body });
+        self.lambdas.len() - 1
+    }
+
+    /// Resolves a list of expressions in the same context
+    fn resolve_exprs(&mut self, exprs: &[Expr], ctxt: ResolveContext) -> Vec<RExpr> {
+        exprs.iter().map(|e| self.resolve_expr(e, ctxt)).collect()
+    }
+
+    /// Resolves an expression into a boxed resolved expression
+    fn resolve_boxed(&mut self, e: &Expr, ctxt: ResolveContext) -> Box<RExpr> {
+        Box::new(self.resolve_expr(e, ctxt))
+    }
+
+    /// Returns the id of a string literal, where equal literals share a single copy
+    fn string(&mut self, s: &str) -> StrId {
+        match self.strings.iter().position(|lit| lit == s) {
+            Some(id) => id,
+            None => {
+                self.strings.push(s.to_string());
+                self.strings.len() - 1
+            },
+        }
+    }
+
+    /// Resolves an expression and its sub-expressions, appending scope errors to errs.
+    /// Expressions in error are resolved as RExprKind::Error nodes.
+    fn resolve_expr(&mut self, e: &Expr, ctxt: ResolveContext) -> RExpr {
+        let kind = match &e.kind {
+            ExprKind::Number(n) => RExprKind::Number(*n),
+            ExprKind::Boolean(b) => RExprKind::Boolean(*b),
+            ExprKind::Str(s) => RExprKind::Str(self.string(s)),
+            ExprKind::Error => RExprKind::Error,
+            ExprKind::Id(s) if s == "input" => RExprKind::Input,
+            // The arguments are read out of their tuple like tuple elements
+            ExprKind::Arg(e_index) => {
+                let args = RExpr { kind: RExprKind::Args, span: e.span };
+                RExprKind::TGet(Box::new(args), self.resolve_boxed(e_index, ctxt))
+            },
+            ExprKind::ArgCount => RExprKind::UnOp(Op1::TupleLength, Box::new(RExpr { kind: RExprKind::Args, span: e.span })),
+            ExprKind::Id(s) => match (ctxt.env.get(s), self.global(s, ctxt), self.funcs.get(s)) {
+                (Some(id), _, _) => {
+                    self.capture(*id);
+                    RExprKind::Var(*id)
+                },
+                // Globals are read where they are stored, so they are never captured
+                (None, Some(gid), _) => RExprKind::Global(gid),
+                (None, None, Some((fid, _, _))) => RExprKind::FuncRef(*fid),
+                (None, None, None) => {
+                    self.errs.push(CompileError::UnboundIdentifier(s.to_string(), e.span));
+                    RExprKind::Error
+                },
+            },
+            ExprKind::Let(binds, body) => {
+                // Each binding sees the bindings before it
+                let mut new_env = ctxt.env.clone();
+                let mut rbinds : Vec<(BindId, RExpr)> = Vec::new();
+                for (i, (binder, expr)) in binds.iter().enumerate() {
+                    let rexpr = self.resolve_expr(expr, ResolveContext { env: &new_env, ..ctxt });
+                    if let Some((first, _)) = binds[..i].iter().find(|(prev, _)| prev.name == binder.name) {
+                        self.errs.push(CompileError::DuplicateBinding(binder.name.clone(), binder.span, first.span));
+                    }
+                    let id = self.bind(binder, BindKind::Local, ctxt.env.get(&binder.name).copied());
+                    new_env.insert(binder.name.clone(), id);
+                    rbinds.push((id, rexpr));
+                }
+                RExprKind::Let(rbinds, self.resolve_boxed(body, ResolveContext { env: &new_env, ..ctxt }))
+            },
+            ExprKind::Set(var, expr) => {
+                let rexpr = self.resolve_boxed(expr, ctxt);
+                match (ctxt.env.get(&var.name), self.global(&var.name, ctxt)) {
+                    (Some(id), _) => {
+                        self.capture(*id);
+                        self.assigned.push((*id, var.span));
+                        RExprKind::Set(*id, rexpr)
+                    },
+                    (None, Some(gid)) => RExprKind::SetGlobal(gid, rexpr),
+                    (None, None) => {
+                        self.errs.push(CompileError::UnboundIdentifier(var.name.clone(), var.span));
+                        RExprKind::Error
+                    },
+                }
+            },
+            ExprKind::Loop(body) => {
+                RExprKind::Loop(self.resolve_boxed(body, ResolveContext { in_loop: true, ..ctxt }))
+            },
+            ExprKind::Break(expr) => {
+                if !ctxt.in_loop {
+                    self.errs.push(CompileError::BreakOutsideLoop(e.span));
+                }
+                RExprKind::Break(self.resolve_boxed(expr, ctxt))
+            },
+            ExprKind::Raise(expr) => RExprKind::Raise(self.resolve_boxed(expr, ctxt)),
+            ExprKind::Assert(cond, msg, text) => {
+                RExprKind::Assert(self.resolve_boxed(cond, ctxt), self.resolve_boxed(msg, ctxt), self.string(text))
+            },
+            ExprKind::Panic(msg, text) => RExprKind::Panic(self.resolve_boxed(msg, ctxt), self.string(text)),
+            ExprKind::Try(body, binder, handler) => {
+                let rbody = self.resolve_boxed(body, ctxt);
+                let mut new_env = ctxt.env.clone();
+                let id = self.bind(binder, BindKind::Local, ctxt.env.get(&binder.name).copied());
+                new_env.insert(binder.name.clone(), id);
+                RExprKind::Try(rbody, id, self.resolve_boxed(handler, ResolveContext { env: &new_env, ..ctxt }))
+            },
+            // Variables holding function values hide functions of the same name
+            ExprKind::Call(fname, exprs) if ctxt.env.contains_key(&fname.name) => {
+                let id = ctxt.env[&fname.name];
+                self.capture(id);
+                let callee = RExpr { kind: RExprKind::Var(id), span: fname.span };
+                RExprKind::CallIndirect(Box::new(callee), self.resolve_exprs(exprs, ctxt))
+            },
+            ExprKind::Call(fname, exprs) if self.global(&fname.name, ctxt).is_some() => {
+                let callee = RExpr { kind: RExprKind::Global(self.globals[&fname.name]), span: fname.span };
+                RExprKind::CallIndirect(Box::new(callee), self.resolve_exprs(exprs, ctxt))
+            },
+            ExprKind::Call(fname, exprs) => {
+                let args = self.resolve_exprs(exprs, ctxt);
+                match self.funcs.get(&fname.name) {
+                    // Optional arguments left out are passed as missing, and the extra arguments
+                    // of a variadic function as one tuple
+                    Some((id, arity, npositional)) if arity.accepts(exprs.len()) => {
+                        let mut args = args;
+                        let extras = args.split_off(args.len().min(*npositional));
+                        args.resize_with(*npositional, || RExpr { kind: RExprKind::MissingArg, span: e.span });
+                        if let Arity::AtLeast(_) = arity {
+                            args.push(RExpr { kind: RExprKind::Tuple(extras), span: e.span });
+                        }
+                        RExprKind::Call(*id, args)
+                    },
+                    Some((_, arity, _)) => {
+                        self.errs.push(CompileError::WrongArity { name: fname.name.clone(), expected: *arity, found: exprs.len(), span: e.span });
+                        RExprKind::Error
+                    },
+                    None => match self.struct_ops.get(&fname.name) {
+                        Some(op) => self.resolve_struct_op(*op, &fname.name, args, e.span),
+                        // A function whose name was reported as invalid is still defined above,
+                        // so only names that nothing can define are unknown operators
+                        None if !is_valid_identifier(&fname.name) && !is_struct_op_name(&fname.name) => {
+                            self.errs.push(CompileError::UnknownOperator(fname.name.clone(), fname.span));
+                            RExprKind::Error
+                        },
+                        None => {
+                            self.errs.push(CompileError::UnknownFunction(fname.name.clone(), fname.span));
+                            RExprKind::Error
+                        },
+                    },
+                }
+            },
+            // Each case sees the variables bound by its own pattern
+            ExprKind::Match(expr, cases) => {
+                let rexpr = self.resolve_boxed(expr, ctxt);
+                let mut rcases : Vec<(RPattern, RExpr)> = Vec::new();
+                for (pattern, body) in cases {
+                    let mut new_env = ctxt.env.clone();
+                    let mut bound : Vec<Binder> = Vec::new();
+                    let rpattern = self.resolve_pattern(pattern, ctxt.env, &mut new_env, &mut bound);
+                    rcases.push((rpattern, self.resolve_expr(body, ResolveContext { env: &new_env, ..ctxt })));
+                }
+                RExprKind::Match(rexpr, rcases)
+            },
+            ExprKind::Apply(callee, exprs) => {
+                RExprKind::CallIndirect(self.resolve_boxed(callee, ctxt), self.resolve_exprs(exprs, ctxt))
+            },
+            ExprKind::Lambda(params, body) => RExprKind::Lambda(self.resolve_lambda(params, body, ctxt)),
+            ExprKind::ApplyTuple(e_func, e_tuple) => {
+                RExprKind::ApplyTuple(self.resolve_boxed(e_func, ctxt), self.resolve_boxed(e_tuple, ctxt))
+            },
+            ExprKind::LetRec(defns, body) => {
+                // The names of the group are bound before any body is resolved, so that the
+                // functions can call each other and themselves through the closures they capture
+                let mut new_env = ctxt.env.clone();
+                let mut ids : Vec<BindId> = Vec::new();
+                for (i, func) in defns.iter().enumerate() {
+                    if let Some(first) = defns[..i].iter().find(|prev| prev.name == func.name) {
+                        self.errs.push(CompileError::DuplicateFunction(func.name.clone(), func.name_span, first.name_span));
+                    }
+                    let binder = Binder { name: func.name.clone(), span: func.name_span };
+                    let id = self.bind(&binder, BindKind::Local, ctxt.env.get(&func.name).copied());
+                    new_env.insert(func.name.clone(), id);
+                    ids.push(id);
+                }
+                let group_ctxt = ResolveContext { env: &new_env, ..ctxt };
+                let rfuncs = ids.into_iter().zip(defns).map(|(id, func)| (id, self.resolve_lambda(&func.params, &func.body, group_ctxt))).collect();
+                RExprKind::LetRec(rfuncs, self.resolve_boxed(body, group_ctxt))
+            },
+            ExprKind::UnOp(op, expr) => RExprKind::UnOp(op.clone(), self.resolve_boxed(expr, ctxt)),
+            ExprKind::BinOp(op, e1, e2) => {
+                RExprKind::BinOp(op.clone(), self.resolve_boxed(e1, ctxt), self.resolve_boxed(e2, ctxt))
+            },
+            ExprKind::TInit(e1, e2) => RExprKind::TInit(self.resolve_boxed(e1, ctxt), self.resolve_boxed(e2, ctxt)),
+            ExprKind::TGet(e1, e2) => RExprKind::TGet(self.resolve_boxed(e1, ctxt), self.resolve_boxed(e2, ctxt)),
+            ExprKind::If(e1, e2, e3) => {
+                RExprKind::If(self.resolve_boxed(e1, ctxt), self.resolve_boxed(e2, ctxt), self.resolve_boxed(e3, ctxt))
+            },
+            ExprKind::And(e1, e2) => RExprKind::And(self.resolve_boxed(e1, ctxt), self.resolve_boxed(e2, ctxt)),
+            ExprKind::Or(e1, e2) => RExprKind::Or(self.resolve_boxed(e1, ctxt), self.resolve_boxed(e2, ctxt)),
+            ExprKind::Cond(clauses, e_else) => {
+                let rclauses = clauses.iter().map(|(test, body)| (self.resolve_expr(test, ctxt), self.resolve_expr(body, ctxt))).collect();
+                RExprKind::Cond(rclauses, self.resolve_boxed(e_else, ctxt))
+            },
+            ExprKind::TSet(e1, e2, e3) => {
+                RExprKind::TSet(self.resolve_boxed(e1, ctxt), self.resolve_boxed(e2, ctxt), self.resolve_boxed(e3, ctxt))
+            },
+            ExprKind::Substring(e1, e2, e3) => {
+                RExprKind::Substring(self.resolve_boxed(e1, ctxt), self.resolve_boxed(e2, ctxt), self.resolve_boxed(e3, ctxt))
+            },
+            ExprKind::TSlice(e1, e2, e3) => {
+                RExprKind::TSlice(self.resolve_boxed(e1, ctxt), self.resolve_boxed(e2, ctxt), self.resolve_boxed(e3, ctxt))
+            },
+            ExprKind::Tuple(exprs) => RExprKind::Tuple(self.resolve_exprs(exprs, ctxt)),
+            ExprKind::Block(exprs) => RExprKind::Block(self.resolve_exprs(exprs, ctxt)),
+        };
+        RExpr { kind, span: e.span }
+    }
+}
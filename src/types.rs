@@ -13,9 +13,11 @@ pub enum Val {
     Imm(i64),
     MemPtr(Reg, i32),
     Label(String),
+    LabelAddr(String),  // rip-relative address of a label
 }
 
 /// Registers: rax, rbx, rsp
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Eq, Hash, Copy, Clone, PartialEq, strum_macros::Display)]
 pub enum Reg {
     RAX,    // main register rax
@@ -24,8 +26,11 @@ pub enum Reg {
     RDX,    // main ERROR CHECKING register
     // REX,    // secondary error check register
     RSP,    // stack pointer
+    RBP,    // stack pointer before an apply, whose frame size is only known at runtime
     RDI,    // stores first integer argument (input)
+    RSI,    // source location of a runtime error
     R15,    // r15 stores the current heap pointer
+    CL,     // low byte of rcx, the count of variable shifts
 }
 
 /// Instruction Types: mov, add, sub, imul
@@ -36,6 +41,8 @@ pub enum Instr {
     Add(Val, Val),
     Sub(Val, Val),
     IMul(Val, Val),
+    IDiv(Val),
+    Cqo,
     CMovl(Val, Val),
     CMovg(Val, Val),
     CMovle(Val, Val),
@@ -44,8 +51,11 @@ pub enum Instr {
     Cmp(Val, Val),
     Test(Val, Val),
     And(Val, Val),
+    Or(Val, Val),
     Xor(Val, Val),
+    Not(Val),
     Sar(Val, Val),
+    Shl(Val, Val),
     Jmp(Val),
     Je(Val),
     Jne(Val),
@@ -53,10 +63,12 @@ pub enum Instr {
     Jle(Val),
     Jg(Val),
     Jge(Val),
+    Ja(Val),
     Jo(Val),
     Push(Val),
     Pop(Val),
     Call(Val),
+    Lea(Val, Val),
     Ret,
 }
 
@@ -66,6 +78,9 @@ pub enum Op1Type {
     Arithmetic,
     TypeCheck,
     Application,
+    String,
+    Logical,
+    Tuple,
 }
 
 /// Unary operators
@@ -76,6 +91,14 @@ pub enum Op1 {
     IsNum,
     IsBool,
     Print,
+    StringLength,
+    NumberToString,
+    StringToNumber,
+    Not,
+    BitNot,
+    TupleLength,
+    IsTuple,
+    TupleCopy,
 }
 
 /// Binary operator types
@@ -84,6 +107,9 @@ pub enum Op2Type {
     Arithmetic,
     Equality,
     Relational,
+    String,
+    Structural,
+    Tuple,
 }
 
 /// Binary operators
@@ -92,47 +118,419 @@ pub enum Op2 {
     Plus,
     Minus,
     Times,
+    Divide,
+    Quotient,
+    Remainder,
+    Modulo,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
     Lt,
     Gt,
     Lte,
     Gte,
     Equal,
+    StructuralEqual,
+    StringAppend,
+    StringEqual,
+    TupleAppend,
+}
+
+/// Region of the source text: byte offsets [start, end) with the line/column of start
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start : usize,
+    pub end : usize,
+    pub line : usize,   // 1-based line of the start offset
+    pub col : usize,    // 1-based column of the start offset
+}
+
+/// Atom of a located s-expression: integer, symbol or string literal
+#[derive(Clone, Debug, PartialEq)]
+pub enum Atom {
+    I(i64),
+    S(String),
+    Str(String),    // contents of a string literal, with escapes already processed
+}
+
+/// Located s-expression produced by the reader, with the span of every atom and list
+#[derive(Clone, Debug)]
+pub enum Sexp {
+    Atom(Atom, Span),
+    List(Vec<Sexp>, Span),
+}
+
+/// Name with the span of one of its occurrences: a binding occurrence (let binding or function
+/// parameter), a set! target or a called function
+#[derive(Clone, Debug)]
+pub struct Binder {
+    pub name : String,
+    pub span : Span,
+}
+
+/// Pattern of a match case, tested against the value being matched
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Wildcard,               // _ matches any value without binding it
+    Number(i64),
+    Boolean(bool),
+    Var(Binder),            // matches any value and binds it
+    Tuple(Vec<Pattern>),    // matches a tuple of exactly this length, element by element
 }
 
 /// Snek Expression Types
 #[derive(Clone, Debug)]
-pub enum Expr {
+pub enum ExprKind {
     Number(i64),
     Boolean(bool),
+    Str(String),
     Tuple(Vec<Expr>),
     Id(String),
-    Let(Vec<(String, Expr)>, Box<Expr>),
+    Arg(Box<Expr>),         // command-line argument at an index, from 0
+    ArgCount,               // number of command-line arguments
+    Let(Vec<(Binder, Expr)>, Box<Expr>),
+    LetRec(Vec<Function>, Box<Expr>),   // local functions, which all see each other, then the body
     UnOp(Op1, Box<Expr>),
     BinOp(Op2, Box<Expr>, Box<Expr>),
     If(Box<Expr>, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),  // short-circuit, the second operand is only evaluated if the first is true
+    Or(Box<Expr>, Box<Expr>),   // short-circuit, the second operand is only evaluated if the first is false
+    Cond(Vec<(Expr, Expr)>, Box<Expr>), // (test body) clauses tried in order, then the else body
     Loop(Box<Expr>),
     Break(Box<Expr>),
-    Set(String, Box<Expr>),
+    Raise(Box<Expr>),
+    Try(Box<Expr>, Binder, Box<Expr>),  // body, then the variable bound to a caught value and the handler
+    Assert(Box<Expr>, Box<Expr>, String),   // condition, message and source text of the assertion
+    Panic(Box<Expr>, String),               // message and source text of the panic
+    Set(Binder, Box<Expr>),
     TInit(Box<Expr>, Box<Expr>),
     TSet(Box<Expr>, Box<Expr>, Box<Expr>),
     TGet(Box<Expr>, Box<Expr>),
+    Substring(Box<Expr>, Box<Expr>, Box<Expr>),
+    TSlice(Box<Expr>, Box<Expr>, Box<Expr>),
     Block(Vec<Expr>),
-    Call(String, Vec<Expr>),
+    Call(Binder, Vec<Expr>),    // call of a function, a function value or an operation generated by a struct
+    Lambda(Params, Box<Expr>),
+    ApplyTuple(Box<Expr>, Box<Expr>),   // call of a function value with the elements of a tuple as arguments
+    Apply(Box<Expr>, Vec<Expr>),    // call of the function value computed by an expression
+    Match(Box<Expr>, Vec<(Pattern, Expr)>),
+    Error,      // placeholder for a malformed expression that was already reported
+}
+
+/// Parameters of a function or lambda: the fixed ones, then the optional ones with the
+/// expressions of their default values, then an optional rest parameter bound to a tuple of the
+/// extra arguments of a call
+#[derive(Clone, Debug)]
+pub struct Params {
+    pub fixed : Vec<Binder>,
+    pub optional : Vec<(Binder, Expr)>,
+    pub rest : Option<Binder>,
+}
+
+/// Number of arguments a function accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exactly(usize),
+    Between(usize, usize),  // fixed parameters followed by optional ones
+    AtLeast(usize),         // fixed parameters followed by a rest parameter
+}
+
+/// Snek Expression with the span of the source it was parsed from
+#[derive(Clone, Debug)]
+pub struct Expr {
+    pub kind : ExprKind,
+    pub span : Span,
 }
 
 /// Function type
-#[derive(Debug)]
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
 pub struct Function {
     pub name : String,
-    pub args : Vec<String>,
+    pub name_span : Span,
+    pub params : Params,
+    pub body : Expr,
+    pub span : Span,
+}
+
+/// Struct declaration type
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Struct {
+    pub name : String,
+    pub name_span : Span,
+    pub fields : Vec<Binder>,
+    pub span : Span,
+}
+
+/// Global definition type
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Global {
+    pub name : String,
+    pub name_span : Span,
     pub body : Expr,
+    pub span : Span,
 }
 
 /// Program type
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct Program {
     pub defns : Vec<Function>,
+    pub structs : Vec<Struct>,
+    pub globals : Vec<Global>,      // in the order their initializers run
     pub main : Expr,
+    pub span : Span,
+}
+
+/// Unique identifier of a variable binding (function parameter or let binding) in a program
+pub type BindId = usize;
+
+/// Unique identifier of a function definition in a program
+pub type FuncId = usize;
+
+/// Unique identifier of a lambda expression in a program
+pub type LambdaId = usize;
+
+/// Unique identifier of a distinct string literal in a program
+pub type StrId = usize;
+
+/// Unique identifier of a struct declaration in a program
+pub type StructId = usize;
+
+/// Unique identifier of a global definition in a program
+pub type GlobalId = usize;
+
+/// Kinds of variable bindings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindKind {
+    Param(usize),   // function or lambda parameter at the given position
+    Local,          // let binding
+}
+
+/// Variable binding found by the resolver
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub name : String,
+    pub span : Span,                // span of the binding occurrence
+    pub kind : BindKind,
+    pub shadows : Option<BindId>,   // outer binding of the same name hidden by this one
+}
+
+/// Pattern of a match case after name resolution, binding each variable to its own binding
+#[derive(Clone, Debug)]
+pub enum RPattern {
+    Wildcard,
+    Number(i64),
+    Boolean(bool),
+    Var(BindId),
+    Tuple(Vec<RPattern>),
+}
+
+/// Snek Expression Types after name resolution, with every variable and
+/// function reference bound to its definition
+#[derive(Clone, Debug)]
+pub enum RExprKind {
+    Number(i64),
+    Boolean(bool),
+    Str(StrId),
+    Tuple(Vec<RExpr>),
+    Input,
+    Args,                                   // tuple of the command-line arguments
+    Var(BindId),
+    Global(GlobalId),
+    Let(Vec<(BindId, RExpr)>, Box<RExpr>),
+    LetRec(Vec<(BindId, LambdaId)>, Box<RExpr>),    // variables bound to the closures of local functions
+    UnOp(Op1, Box<RExpr>),
+    BinOp(Op2, Box<RExpr>, Box<RExpr>),
+    If(Box<RExpr>, Box<RExpr>, Box<RExpr>),
+    And(Box<RExpr>, Box<RExpr>),
+    Or(Box<RExpr>, Box<RExpr>),
+    Cond(Vec<(RExpr, RExpr)>, Box<RExpr>),
+    Loop(Box<RExpr>),
+    Break(Box<RExpr>),
+    Raise(Box<RExpr>),
+    Try(Box<RExpr>, BindId, Box<RExpr>),
+    Assert(Box<RExpr>, Box<RExpr>, StrId),
+    Panic(Box<RExpr>, StrId),
+    Set(BindId, Box<RExpr>),
+    SetGlobal(GlobalId, Box<RExpr>),
+    TInit(Box<RExpr>, Box<RExpr>),
+    TSet(Box<RExpr>, Box<RExpr>, Box<RExpr>),
+    TGet(Box<RExpr>, Box<RExpr>),
+    Substring(Box<RExpr>, Box<RExpr>, Box<RExpr>),
+    TSlice(Box<RExpr>, Box<RExpr>, Box<RExpr>),
+    Block(Vec<RExpr>),
+    Call(FuncId, Vec<RExpr>),
+    MissingArg,                             // optional argument left out of a direct call, filled in by the callee
+    Lambda(LambdaId),
+    FuncRef(FuncId),                        // function definition used as a value
+    CallIndirect(Box<RExpr>, Vec<RExpr>),   // call of a function value
+    ApplyTuple(Box<RExpr>, Box<RExpr>),     // call of a function value with the elements of a tuple
+    StructNew(StructId, Vec<RExpr>),                    // constructor, with the field values in order
+    StructIs(StructId, Box<RExpr>),                     // type predicate
+    StructGet(StructId, usize, Box<RExpr>),             // accessor of the field at the given position
+    StructSet(StructId, usize, Box<RExpr>, Box<RExpr>), // mutator of the field at the given position
+    Match(Box<RExpr>, Vec<(RPattern, RExpr)>),          // cases tried in order
+    Error,      // placeholder for an expression that was already reported as an error
+}
+
+/// Resolved Snek Expression with the span of the source it was parsed from
+#[derive(Clone, Debug)]
+pub struct RExpr {
+    pub kind : RExprKind,
+    pub span : Span,
+}
+
+/// Resolved function type
+#[derive(Debug)]
+pub struct RFunction {
+    pub name : String,
+    pub name_span : Span,
+    pub params : Vec<BindId>,
+    pub defaults : Vec<RExpr>,      // default values of the optional parameters, which follow the fixed ones
+    pub variadic : bool,            // whether the last parameter is a rest parameter
+    pub body : RExpr,
+}
+
+/// Resolved lambda expression, lifted out of the expression it appears in
+#[derive(Debug)]
+pub struct RLambda {
+    pub params : Vec<BindId>,
+    pub defaults : Vec<RExpr>,      // default values of the optional parameters, which follow the fixed ones
+    pub variadic : bool,            // whether the last parameter is a rest parameter
+    pub captures : Vec<BindId>,     // variables of enclosing scopes used in the body, copied into the closure
+    pub body : RExpr,
+}
+
+/// Resolved struct declaration
+#[derive(Debug)]
+pub struct RStruct {
+    pub name : String,
+    pub fields : Vec<String>,
+}
+
+/// Resolved program type, compiled only when resolution found no static errors
+#[derive(Debug)]
+pub struct RProgram {
+    pub funcs : Vec<RFunction>,     // indexed by FuncId
+    pub lambdas : Vec<RLambda>,     // indexed by LambdaId
+    pub strings : Vec<String>,      // distinct string literals, indexed by StrId
+    pub structs : Vec<RStruct>,     // indexed by StructId
+    pub globals : Vec<RExpr>,       // initializers of the globals, indexed by GlobalId and run in that order
+    pub main : RExpr,
+    pub bindings : Vec<Binding>,    // indexed by BindId
+}
+
+/// Static errors raised while parsing or compiling a Snek program
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    InvalidSexp(String, Span),          // source is not a well-formed s-expression
+    EmptyProgram(Span),                 // no definitions and no main expression
+    MissingMain(Span),                  // program ends with a definition instead of an expression
+    InvalidDefinition(Span),            // definition not of the form (fun (name args...) body)
+    InvalidIdentifier(String, Span),    // name does not follow identifier naming conventions
+    KeywordMisuse(String, Span),        // keyword used as a name
+    DuplicateFunction(String, Span, Span),  // function name defined more than once (with first definition)
+    DuplicateParameter(String, Span, Span), // parameter repeated in a function definition (with first occurrence)
+    DuplicateBinding(String, Span, Span),   // identifier repeated in a single let (with first binding)
+    NumberOutOfRange(String, Span),     // integer literal outside of Snek bounds
+    InvalidForm(String, Span),          // malformed keyword form (let, if, block, ...)
+    UnknownOperator(String, Span),      // list head is neither an operator nor a name
+    UnknownFunction(String, Span),      // call to a function that is not defined
+    UnboundIdentifier(String, Span),    // identifier not in scope
+    WrongArity { name: String, expected: Arity, found: usize, span: Span },
+    BreakOutsideLoop(Span),             // break not enclosed by a loop
+    DuplicateField(String, Span, Span), // field repeated in a struct declaration (with first occurrence)
+    InvalidPattern(Span),               // match pattern that is not a literal, name, _ or tuple of patterns
+    DuplicateGlobal(String, Span, Span),    // global name defined more than once or also a function (with first definition)
+    CapturedAssignment(String, Span, Span), // set! on a variable captured by a lambda (with its binding)
+}
+
+/// Lints that can each be allowed, warned about or denied from the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedBinding,      // let binding that is never read
+    UnusedParameter,    // function parameter that is never read
+    UnusedFunction,     // function that cannot be reached from the main expression
+    ShadowedBinding,    // let binding that hides a parameter or an outer let binding
+    DefiniteFailure,    // operation whose runtime type check always fails
+}
+
+/// Level at which a lint is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,  // not reported
+    Warn,   // reported as a warning
+    Deny,   // reported as an error, failing the compilation
+}
+
+/// Kind of a Snek value, as distinguished by the runtime type checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Number,
+    Boolean,
+    Tuple,
+    Function,
+    String,
+    Record,
+}
+
+/// Warnings raised by the lint passes over a well-formed Snek program
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    UnusedBinding(String, Span),
+    UnusedParameter(String, Span),
+    UnusedFunction(String, Span),
+    ShadowedBinding { name: String, span: Span, outer: Span },
+    // Operation (span) given an argument (arg) that is always of the found kind
+    DefiniteFailure { op: String, expected: ValueKind, found: ValueKind, span: Span, arg: Span },
+}
+
+/// Severity of a reported diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Secondary location attached to a diagnostic, e.g. where a duplicate name was first bound
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span : Span,
+    pub message : String,
+}
+
+/// Error or warning ready to be reported, independent of the output format
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code : &'static str,        // stable error or lint code, e.g. E0012
+    pub name : &'static str,        // kebab-case name of the code, e.g. duplicate-binding
+    pub severity : Severity,
+    pub message : String,
+    pub span : Span,                // primary location
+    pub labels : Vec<Label>,        // secondary locations
+    pub help : Option<String>,      // suggestion on how to fix the problem
+}
+
+/// Long-form description of an error or lint code, printed by --explain
+pub struct Explanation {
+    pub code : &'static str,
+    pub name : &'static str,
+    pub summary : &'static str,     // one-line description
+    pub description : &'static str,
+    pub failing : &'static str,     // minimal program that raises the error
+    pub fixed : &'static str,       // the same program with the error fixed
+}
+
+/// Output format for diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Human,  // text for a person reading the terminal
+    Json,   // one JSON object per line for tools
 }
 
 /// Location pointer type -- either register or stack memory
@@ -149,6 +547,9 @@ pub enum ValCheck {
     Integer,
     Boolean,
     Tuple,
+    Closure,
+    String,
+    Record,
     Equality,
 }
 
@@ -163,10 +564,13 @@ pub enum TailContext {
 #[derive(Copy, Clone)]
 pub struct ExprContext<'a> {
     pub si : i32,                           // current stack index
-    pub env : &'a HashMap<String, LocPtr>,  // variable environment
+    pub env : &'a HashMap<BindId, LocPtr>,  // variable environment
     pub loop_num : i32,                     // current loop identifier
-    pub func_map : &'a HashMap<String,i32>, // function name map
+    pub loop_handler : Option<i32>,         // stack index of the outermost handler installed inside the current loop
+    pub funcs : &'a [RFunction],            // functions of the program, indexed by FuncId
+    pub lambdas : &'a [RLambda],            // lambdas of the program, indexed by LambdaId
     pub in_func : bool,                     // whether inside a function or not
     pub tail : TailContext,                 // whether a tail call can occur within this expression
-    pub farity : usize                      // how many function arguments
+    pub farity : usize,                     // how many function arguments
+    pub release : bool,                     // whether assertions are compiled out
 }
\ No newline at end of file
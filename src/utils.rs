@@ -6,7 +6,7 @@
 
 use crate::types::*;
 
-// use std::fmt;
+use std::fmt;
 use std::cmp::max;
 use std::collections::HashSet;
 
@@ -25,12 +25,64 @@ pub const OF_ERRCODE : i64 = 8;
 /// bounds error code
 pub const BND_ERRCODE : i64 = 9;
 
+/// arity error code (function value called with the wrong number of arguments)
+pub const ARITY_ERRCODE : i64 = 10;
+
+/// struct error code (record of another struct than the one expected)
+pub const STRUCT_ERRCODE : i64 = 11;
+
+/// match error code (no case of a match expression matched the value)
+pub const MATCH_ERRCODE : i64 = 12;
+
+/// division error code (division or remainder by zero)
+pub const DIV_ERRCODE : i64 = 13;
+
+/// global error code (global read before its initializer has run)
+pub const GLOBAL_ERRCODE : i64 = 14;
+
+/// raise error code (value raised outside of any try)
+pub const RAISE_ERRCODE : i64 = 15;
+
+/// stable codes of the runtime errors, indexed by error code from MSMX_ERRCODE on. A handler
+/// catches a built-in runtime error as the string of its code.
+pub const RUNTIME_ERROR_CODES : [&str; 9] = ["R0001", "R0002", "R0003", "R0004", "R0005", "R0006", "R0007", "R0008", "R0009"];
+
+/// mask of the tag bits of heap-allocated values
+pub const HEAP_TAG_MASK : i64 = 0b1111;
+
+/// tuple tag (heap address + tag)
+pub const TUPLE_TAG : i64 = 0b0001;
+
+/// closure tag (heap address + tag)
+pub const CLOSURE_TAG : i64 = 0b0101;
+
+/// string tag (heap or data address + tag)
+pub const STRING_TAG : i64 = 0b1001;
+
+/// record tag (heap address + tag)
+pub const RECORD_TAG : i64 = 0b1101;
+
+/// alignment of heap objects in bytes, leaving room for the heap tags
+pub const HEAP_ALIGN : i32 = 16;
+
 /// true  value representation (code + tag)
 pub const TRUE_VAL  : i64 = 7;
 
 /// false value representation (code + tag)
 pub const FALSE_VAL : i64 = 3;
 
+/// value passed for an optional argument left out of a call, which is not the value of any
+/// expression (its tag is unused)
+pub const MISSING_ARG : i64 = 0b1111;
+
+/// added to how many more arguments than the least a closure with a rest parameter accepts,
+/// which is more than any call passes
+pub const REST_ARITY : i64 = 1 << 30;
+
+/// value a global holds until its initializer has run, which is not the value of any expression
+/// (its tag is unused)
+pub const UNSET_GLOBAL : i64 = 0b1_1111;
+
 /// whether or not there should be tail call optimization
 pub const START_TAIL : TailContext = TailContext::Valid;
 
@@ -43,6 +95,16 @@ lazy_static! {
             String::from("isnum"),
             String::from("isbool"),
             String::from("*"),
+            String::from("/"),
+            String::from("quotient"),
+            String::from("remainder"),
+            String::from("modulo"),
+            String::from("bitand"),
+            String::from("bitor"),
+            String::from("bitxor"),
+            String::from("bitnot"),
+            String::from("shift-left"),
+            String::from("shift-right"),
             String::from("-"),
             String::from("+"),
             String::from("<"),
@@ -50,17 +112,51 @@ lazy_static! {
             String::from("<="),
             String::from(">="),
             String::from("="),
+            String::from("equal?"),
             String::from("let"),
+            String::from("letrec"),
             String::from("if"),
             String::from("set!"),
             String::from("block"),
             String::from("loop"),
             String::from("break"),
+            String::from("raise"),
+            String::from("try"),
+            String::from("catch"),
+            String::from("assert"),
+            String::from("panic"),
             String::from("true"),
             String::from("false"),
             String::from("input"),
+            String::from("arg"),
+            String::from("argc"),
             String::from("print"),
             String::from("fun"),
+            String::from("tuple"),
+            String::from("tinit"),
+            String::from("tset"),
+            String::from("tget"),
+            String::from("tlen"),
+            String::from("istuple"),
+            String::from("tslice"),
+            String::from("tappend"),
+            String::from("tcopy"),
+            String::from("lambda"),
+            String::from("apply"),
+            String::from("struct"),
+            String::from("def"),
+            String::from("match"),
+            String::from("and"),
+            String::from("or"),
+            String::from("not"),
+            String::from("cond"),
+            String::from("else"),
+            String::from("substring"),
+            String::from("string-length"),
+            String::from("string-append"),
+            String::from("string=?"),
+            String::from("number->string"),
+            String::from("string->number"),
         ])
     };
 }
@@ -87,13 +183,380 @@ impl LocPtr {
     }
 }
 
+impl Sexp {
+    /// Span of the source text covered by this s-expression
+    pub fn span(&self) -> Span {
+        match self {
+            Sexp::Atom(_, span) | Sexp::List(_, span) => *span,
+        }
+    }
+}
+
+impl Span {
+    /// Location packed into a single word for runtime error reports (line << 32 | col)
+    pub fn packed(&self) -> i64 {
+        ((self.line as i64) << 32) | self.col as i64
+    }
+}
+
+impl CompileError {
+    /// Short kebab-case name of the error kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CompileError::InvalidSexp(..) => "invalid-sexp",
+            CompileError::EmptyProgram(_) => "empty-program",
+            CompileError::MissingMain(_) => "missing-main",
+            CompileError::InvalidDefinition(_) => "invalid-definition",
+            CompileError::InvalidIdentifier(..) => "invalid-identifier",
+            CompileError::KeywordMisuse(..) => "keyword-misuse",
+            CompileError::DuplicateFunction(..) => "duplicate-function",
+            CompileError::DuplicateParameter(..) => "duplicate-parameter",
+            CompileError::DuplicateBinding(..) => "duplicate-binding",
+            CompileError::NumberOutOfRange(..) => "number-out-of-range",
+            CompileError::InvalidForm(..) => "invalid-form",
+            CompileError::UnknownOperator(..) => "unknown-operator",
+            CompileError::UnknownFunction(..) => "unknown-function",
+            CompileError::UnboundIdentifier(..) => "unbound-identifier",
+            CompileError::WrongArity { .. } => "wrong-arity",
+            CompileError::BreakOutsideLoop(_) => "break-outside-loop",
+            CompileError::DuplicateField(..) => "duplicate-field",
+            CompileError::InvalidPattern(_) => "invalid-pattern",
+            CompileError::DuplicateGlobal(..) => "duplicate-global",
+            CompileError::CapturedAssignment(..) => "captured-assignment",
+        }
+    }
+
+    /// Stable code of the error kind, explained by --explain
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompileError::InvalidSexp(..) => "E0001",
+            CompileError::EmptyProgram(_) => "E0002",
+            CompileError::MissingMain(_) => "E0003",
+            CompileError::InvalidDefinition(_) => "E0004",
+            CompileError::InvalidForm(..) => "E0005",
+            CompileError::UnknownOperator(..) => "E0006",
+            CompileError::NumberOutOfRange(..) => "E0007",
+            CompileError::InvalidIdentifier(..) => "E0008",
+            CompileError::KeywordMisuse(..) => "E0009",
+            CompileError::DuplicateFunction(..) => "E0010",
+            CompileError::DuplicateParameter(..) => "E0011",
+            CompileError::DuplicateBinding(..) => "E0012",
+            CompileError::UnboundIdentifier(..) => "E0013",
+            CompileError::UnknownFunction(..) => "E0014",
+            CompileError::WrongArity { .. } => "E0015",
+            CompileError::BreakOutsideLoop(_) => "E0017",
+            CompileError::DuplicateField(..) => "E0018",
+            CompileError::InvalidPattern(_) => "E0019",
+            CompileError::DuplicateGlobal(..) => "E0020",
+            CompileError::CapturedAssignment(..) => "E0021",
+        }
+    }
+
+    /// Span of the source that caused the error
+    pub fn span(&self) -> Span {
+        match self {
+            CompileError::EmptyProgram(span) | CompileError::MissingMain(span) |
+            CompileError::InvalidDefinition(span) |
+            CompileError::BreakOutsideLoop(span) | CompileError::WrongArity { span, .. } |
+            CompileError::InvalidPattern(span) |
+            CompileError::InvalidSexp(_, span) | CompileError::InvalidIdentifier(_, span) |
+            CompileError::KeywordMisuse(_, span) | CompileError::DuplicateFunction(_, span, _) |
+            CompileError::DuplicateParameter(_, span, _) | CompileError::DuplicateBinding(_, span, _) |
+            CompileError::DuplicateField(_, span, _) | CompileError::DuplicateGlobal(_, span, _) |
+            CompileError::CapturedAssignment(_, span, _) |
+            CompileError::NumberOutOfRange(_, span) | CompileError::InvalidForm(_, span) |
+            CompileError::UnknownOperator(_, span) | CompileError::UnknownFunction(_, span) |
+            CompileError::UnboundIdentifier(_, span) => *span,
+        }
+    }
+
+    /// Secondary spans related to the error, such as the first occurrence of a duplicate name
+    pub fn labels(&self) -> Vec<Label> {
+        match self {
+            CompileError::DuplicateFunction(_, _, first) | CompileError::DuplicateGlobal(_, _, first) =>
+                vec![Label { span: *first, message: String::from("first defined here") }],
+            CompileError::DuplicateParameter(_, _, first) | CompileError::DuplicateBinding(_, _, first) =>
+                vec![Label { span: *first, message: String::from("first bound here") }],
+            CompileError::DuplicateField(_, _, first) =>
+                vec![Label { span: *first, message: String::from("first declared here") }],
+            CompileError::CapturedAssignment(_, _, binding) =>
+                vec![Label { span: *binding, message: String::from("bound here") }],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Suggestion on how to fix the error, if there is a useful one
+    pub fn help(&self) -> Option<String> {
+        match self {
+            CompileError::InvalidDefinition(_) =>
+                Some(String::from("functions are defined as (fun (name param ... (param default) ...) body)")),
+            CompileError::MissingMain(_) =>
+                Some(String::from("add an expression after the function definitions")),
+            CompileError::InvalidForm(form, _) => {
+                let shape = match form.as_str() {
+                    "let" => "(let ((name expr) ...) body)",
+                    "if" => "(if cond then else)",
+                    "loop" => "(loop body)",
+                    "break" => "(break expr)",
+                    "raise" => "(raise expr)",
+                    "try" => "(try body (catch name handler))",
+                    "assert" => "(assert cond) or (assert cond message)",
+                    "panic" => "(panic message)",
+                    "set!" => "(set! name expr)",
+                    "block" => "(block expr ...) with at least one expression",
+                    "lambda" => "(lambda (param ... (param default) ...) body), optionally with . rest after the parameters",
+                    "letrec" => "(letrec ((fun (name param ...) body) ...) body)",
+                    "struct" => "(struct name (field ...)) at the top level",
+                    "def" => "(def name expr) at the top level",
+                    "match" => "(match expr (pattern body) ...) with at least one case",
+                    "cond" => "(cond (test body) ... (else body))",
+                    "()" => return Some(String::from("the empty list is not an expression")),
+                    _ => return None,
+                };
+                Some(format!("{form} is written {shape}"))
+            },
+            CompileError::UnknownOperator(..) =>
+                Some(String::from("a list must start with a keyword, an operator, a name or an expression computing a function")),
+            CompileError::InvalidIdentifier(..) =>
+                Some(String::from("names start with a letter, followed only by letters and digits")),
+            CompileError::KeywordMisuse(..) =>
+                Some(String::from("keywords are reserved, choose a different name")),
+            CompileError::DuplicateFunction(..) | CompileError::DuplicateParameter(..) | CompileError::DuplicateField(..) |
+            CompileError::DuplicateGlobal(..) =>
+                Some(String::from("rename one of them")),
+            CompileError::NumberOutOfRange(..) =>
+                Some(format!("Snek integers must be between {} and {}", -LIM, LIM - 1)),
+            CompileError::DuplicateBinding(..) =>
+                Some(String::from("rename one of the bindings or use a nested let")),
+            CompileError::BreakOutsideLoop(_) =>
+                Some(String::from("break can only be used inside the body of a (loop ...)")),
+            CompileError::InvalidPattern(_) =>
+                Some(String::from("patterns are _, a name, a number, true, false or (tuple pattern ...)")),
+            CompileError::CapturedAssignment(..) =>
+                Some(String::from("lambdas copy the variables they capture; keep a value they share in a tuple, a record or a global")),
+            _ => None,
+        }
+    }
+
+    /// Converts the error into a diagnostic for reporting
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            code: self.code(),
+            name: self.kind(),
+            severity: Severity::Error,
+            message: self.to_string(),
+            span: self.span(),
+            labels: self.labels(),
+            help: self.help(),
+        }
+    }
+}
+
+impl Lint {
+    /// Every lint, in the order they are documented
+    pub const ALL : [Lint; 5] = [
+        Lint::UnusedBinding, Lint::UnusedParameter, Lint::UnusedFunction, Lint::ShadowedBinding, Lint::DefiniteFailure,
+    ];
+
+    /// Stable code of the lint, explained by --explain
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lint::UnusedBinding => "W0001",
+            Lint::UnusedParameter => "W0002",
+            Lint::UnusedFunction => "W0003",
+            Lint::ShadowedBinding => "W0004",
+            Lint::DefiniteFailure => "W0005",
+        }
+    }
+
+    /// Kebab-case name of the lint, used by the -W/-A/-D flags
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedBinding => "unused-binding",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::UnusedFunction => "unused-function",
+            Lint::ShadowedBinding => "shadowed-binding",
+            Lint::DefiniteFailure => "definite-failure",
+        }
+    }
+
+    /// Level of the lint when no -W/-A/-D flag sets it
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            Lint::DefiniteFailure => LintLevel::Deny,
+            _ => LintLevel::Warn,
+        }
+    }
+
+    /// Looks up a lint by its name or code
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name || lint.code() == name)
+    }
+}
+
+impl Warning {
+    /// Lint that raised the warning
+    pub fn lint(&self) -> Lint {
+        match self {
+            Warning::UnusedBinding(..) => Lint::UnusedBinding,
+            Warning::UnusedParameter(..) => Lint::UnusedParameter,
+            Warning::UnusedFunction(..) => Lint::UnusedFunction,
+            Warning::ShadowedBinding { .. } => Lint::ShadowedBinding,
+            Warning::DefiniteFailure { .. } => Lint::DefiniteFailure,
+        }
+    }
+
+    /// Span of the source text the warning is about
+    pub fn span(&self) -> Span {
+        match self {
+            Warning::UnusedBinding(_, span) | Warning::UnusedParameter(_, span) |
+            Warning::UnusedFunction(_, span) | Warning::ShadowedBinding { span, .. } |
+            Warning::DefiniteFailure { span, .. } => *span,
+        }
+    }
+
+    /// Converts the warning into a diagnostic reported at the given (non-allow) lint level
+    pub fn to_diagnostic(&self, level: LintLevel) -> Diagnostic {
+        let (labels, help) = match self {
+            Warning::UnusedBinding(..) => (Vec::new(), "remove the binding, or evaluate the expression in a block if it has effects"),
+            Warning::UnusedParameter(..) => (Vec::new(), "remove the parameter and the corresponding argument at each call"),
+            Warning::UnusedFunction(..) => (Vec::new(), "remove the function if it is no longer needed"),
+            Warning::ShadowedBinding { name, outer, .. } => (
+                vec![Label { span: *outer, message: format!("'{name}' was previously bound here") }],
+                "rename one of the bindings",
+            ),
+            Warning::DefiniteFailure { found, arg, .. } => (
+                vec![Label { span: *arg, message: format!("this is always a {found}") }],
+                "this operation stops the program with an invalid-argument error (R0001) whenever it runs",
+            ),
+        };
+        Diagnostic {
+            code: self.lint().code(),
+            name: self.lint().name(),
+            severity: if level == LintLevel::Deny { Severity::Error } else { Severity::Warning },
+            message: self.to_string(),
+            span: self.span(),
+            labels,
+            help: Some(String::from(help)),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    /// Display method for Warning (human-readable message)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::UnusedBinding(s, _) => write!(f, "unused variable '{s}'"),
+            Warning::UnusedParameter(s, _) => write!(f, "unused parameter '{s}'"),
+            Warning::UnusedFunction(s, _) => write!(f, "function '{s}' is never called from the main expression"),
+            Warning::ShadowedBinding { name, .. } => write!(f, "'{name}' shadows an earlier binding"),
+            Warning::DefiniteFailure { op, expected, found, .. } =>
+                write!(f, "'{op}' always fails: expected a {expected}, found a {found}"),
+        }
+    }
+}
+
+impl Arity {
+    /// Whether a call with the given number of arguments is allowed
+    pub fn accepts(self, nargs: usize) -> bool {
+        match self {
+            Arity::Exactly(n) => nargs == n,
+            Arity::Between(min, max) => (min..=max).contains(&nargs),
+            Arity::AtLeast(n) => nargs >= n,
+        }
+    }
+}
+
+impl Params {
+    /// Number of arguments accepted by a function with these parameters
+    pub fn arity(&self) -> Arity {
+        match self.rest {
+            Some(_) => Arity::AtLeast(self.fixed.len()),
+            None if self.optional.is_empty() => Arity::Exactly(self.fixed.len()),
+            None => Arity::Between(self.fixed.len(), self.fixed.len() + self.optional.len()),
+        }
+    }
+
+    /// Every parameter in order, with the optional parameters after the fixed ones and the rest parameter last
+    pub fn binders(&self) -> impl Iterator<Item = &Binder> {
+        self.fixed.iter().chain(self.optional.iter().map(|(param, _)| param)).chain(&self.rest)
+    }
+}
+
+impl fmt::Display for Arity {
+    /// Display method for Arity (as in "expects at least 2 argument(s)")
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arity::Exactly(n) => write!(f, "{n}"),
+            Arity::Between(min, max) => write!(f, "{min} to {max}"),
+            Arity::AtLeast(n) => write!(f, "at least {n}"),
+        }
+    }
+}
+
+impl fmt::Display for ValueKind {
+    /// Display method for ValueKind (as named in messages)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueKind::Number => write!(f, "number"),
+            ValueKind::Boolean => write!(f, "boolean"),
+            ValueKind::Tuple => write!(f, "tuple"),
+            ValueKind::Function => write!(f, "function"),
+            ValueKind::String => write!(f, "string"),
+            ValueKind::Record => write!(f, "record"),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    /// Display method for Severity (as written before a diagnostic)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    /// Display method for CompileError (human-readable message)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::InvalidSexp(msg, _) => write!(f, "invalid s-expression: {msg}"),
+            CompileError::EmptyProgram(_) => write!(f, "program is empty"),
+            CompileError::MissingMain(_) => write!(f, "program has no main expression"),
+            CompileError::InvalidDefinition(_) => write!(f, "expected a function definition (fun (name args...) body)"),
+            CompileError::InvalidIdentifier(s, _) => write!(f, "invalid identifier '{s}'"),
+            CompileError::KeywordMisuse(s, _) => write!(f, "keyword '{s}' cannot be used as a name"),
+            CompileError::DuplicateFunction(s, ..) => write!(f, "duplicate function name '{s}'"),
+            CompileError::DuplicateParameter(s, ..) => write!(f, "duplicate parameter name '{s}'"),
+            CompileError::DuplicateBinding(s, ..) => write!(f, "duplicate binding '{s}'"),
+            CompileError::NumberOutOfRange(s, _) => write!(f, "integer literal {s} is out of range"),
+            CompileError::InvalidForm(s, _) => write!(f, "malformed '{s}' expression"),
+            CompileError::UnknownOperator(s, _) => write!(f, "unknown operator '{s}'"),
+            CompileError::UnknownFunction(s, _) => write!(f, "unknown function '{s}'"),
+            CompileError::UnboundIdentifier(s, _) => write!(f, "unbound variable identifier {s}"),
+            CompileError::WrongArity { name, expected, found, .. } =>
+                write!(f, "'{name}' expects {expected} argument(s) but was given {found}"),
+            CompileError::BreakOutsideLoop(_) => write!(f, "break outside of loop"),
+            CompileError::DuplicateField(s, ..) => write!(f, "duplicate field name '{s}'"),
+            CompileError::InvalidPattern(_) => write!(f, "invalid pattern"),
+            CompileError::DuplicateGlobal(s, ..) => write!(f, "duplicate global name '{s}'"),
+            CompileError::CapturedAssignment(s, ..) => write!(f, "cannot assign to '{s}', which is captured by a lambda"),
+        }
+    }
+}
+
 impl Op1 {
     /// Get Op1Type from Op1
     pub fn get_type(&self) -> Op1Type {
         match self {
-            Op1::Add1 | Op1::Sub1 => Op1Type::Arithmetic,
-            Op1::IsNum | Op1::IsBool => Op1Type::TypeCheck,
+            Op1::Add1 | Op1::Sub1 | Op1::BitNot => Op1Type::Arithmetic,
+            Op1::IsNum | Op1::IsBool | Op1::IsTuple => Op1Type::TypeCheck,
             Op1::Print => Op1Type::Application,
+            Op1::StringLength | Op1::NumberToString | Op1::StringToNumber => Op1Type::String,
+            Op1::Not => Op1Type::Logical,
+            Op1::TupleLength | Op1::TupleCopy => Op1Type::Tuple,
         }
     }
 }
@@ -102,35 +565,103 @@ impl Op2 {
     /// Get Op2Type from Op2
     pub fn get_type(&self) -> Op2Type {
         match self {
-            Op2::Plus | Op2::Minus | Op2::Times => Op2Type::Arithmetic,
+            Op2::Plus | Op2::Minus | Op2::Times | Op2::Divide | Op2::Quotient | Op2::Remainder | Op2::Modulo |
+            Op2::BitAnd | Op2::BitOr | Op2::BitXor | Op2::ShiftLeft | Op2::ShiftRight => Op2Type::Arithmetic,
             Op2::Lt | Op2::Gt | Op2::Lte | Op2::Gte => Op2Type::Relational,
             Op2::Equal => Op2Type::Equality,
+            Op2::StructuralEqual => Op2Type::Structural,
+            Op2::StringAppend | Op2::StringEqual => Op2Type::String,
+            Op2::TupleAppend => Op2Type::Tuple,
         }
     }
 }
 
+impl fmt::Display for Op1 {
+    /// Display method for Op1 (as written in the source)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Op1::Add1 => write!(f, "add1"),
+            Op1::Sub1 => write!(f, "sub1"),
+            Op1::IsNum => write!(f, "isnum"),
+            Op1::IsBool => write!(f, "isbool"),
+            Op1::Print => write!(f, "print"),
+            Op1::StringLength => write!(f, "string-length"),
+            Op1::NumberToString => write!(f, "number->string"),
+            Op1::StringToNumber => write!(f, "string->number"),
+            Op1::Not => write!(f, "not"),
+            Op1::BitNot => write!(f, "bitnot"),
+            Op1::TupleLength => write!(f, "tlen"),
+            Op1::IsTuple => write!(f, "istuple"),
+            Op1::TupleCopy => write!(f, "tcopy"),
+        }
+    }
+}
+
+impl fmt::Display for Op2 {
+    /// Display method for Op2 (as written in the source)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Op2::Plus => write!(f, "+"),
+            Op2::Minus => write!(f, "-"),
+            Op2::Times => write!(f, "*"),
+            Op2::Divide => write!(f, "/"),
+            Op2::Quotient => write!(f, "quotient"),
+            Op2::Remainder => write!(f, "remainder"),
+            Op2::Modulo => write!(f, "modulo"),
+            Op2::BitAnd => write!(f, "bitand"),
+            Op2::BitOr => write!(f, "bitor"),
+            Op2::BitXor => write!(f, "bitxor"),
+            Op2::ShiftLeft => write!(f, "shift-left"),
+            Op2::ShiftRight => write!(f, "shift-right"),
+            Op2::Lt => write!(f, "<"),
+            Op2::Gt => write!(f, ">"),
+            Op2::Lte => write!(f, "<="),
+            Op2::Gte => write!(f, ">="),
+            Op2::Equal => write!(f, "="),
+            Op2::StructuralEqual => write!(f, "equal?"),
+            Op2::StringAppend => write!(f, "string-append"),
+            Op2::StringEqual => write!(f, "string=?"),
+            Op2::TupleAppend => write!(f, "tappend"),
+        }
+    }
+}
+
+/// Returns instructions that load the error code and source location of a runtime error check
+fn error_site(errcode : i64, span : &Span) -> Vec<Instr> {
+    vec![
+        Instr::Mov(Val::Reg(Reg::RDX), Val::Imm(errcode)),
+        Instr::Mov(Val::Reg(Reg::RSI), Val::Imm(span.packed())),
+    ]
+}
+
 /// Returns instructions that perform a runtime mismatch error check
-pub fn check_msmx(check : Val, check2_opn : Option<Val>, ctype : ValCheck, lbl : &mut i32) -> Vec<Instr> {
+pub fn check_msmx(check : Val, check2_opn : Option<Val>, ctype : ValCheck, span : &Span, lbl : &mut i32) -> Vec<Instr> {
     let mut ret : Vec<Instr> = Vec::new();
-    let err_val = Val::Label(format!("throw_error_align"));
+    let err_val = Val::Label(String::from("throw_error_align"));
     match ctype {
         ValCheck::Integer => {
             ret.push(Instr::Test(check, Val::Imm(1)));
-            ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Imm(MSMX_ERRCODE)));
+            ret.append(&mut error_site(MSMX_ERRCODE, span));
             ret.push(Instr::Jne(err_val));
         },
         ValCheck::Boolean => {
             ret.push(Instr::Mov(Val::Reg(Reg::RDX), check));
             ret.push(Instr::And(Val::Reg(Reg::RDX), Val::Imm(0b11)));
             ret.push(Instr::Cmp(Val::Reg(Reg::RDX), Val::Imm(0b11)));
-            ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Imm(MSMX_ERRCODE)));
+            ret.append(&mut error_site(MSMX_ERRCODE, span));
             ret.push(Instr::Jne(err_val));
         },
-        ValCheck::Tuple => {
+        ValCheck::Tuple | ValCheck::Closure | ValCheck::String | ValCheck::Record => {
+            let tag = match ctype {
+                ValCheck::Tuple => TUPLE_TAG,
+                ValCheck::Closure => CLOSURE_TAG,
+                ValCheck::String => STRING_TAG,
+                _ => RECORD_TAG,
+            };
             ret.push(Instr::Mov(Val::Reg(Reg::RDX), check));
-            ret.push(Instr::And(Val::Reg(Reg::RDX), Val::Imm(0b11)));
-            ret.push(Instr::Cmp(Val::Reg(Reg::RDX), Val::Imm(0b01)));
-            ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Imm(MSMX_ERRCODE)));
+            ret.push(Instr::And(Val::Reg(Reg::RDX), Val::Imm(HEAP_TAG_MASK)));
+            ret.push(Instr::Cmp(Val::Reg(Reg::RDX), Val::Imm(tag)));
+            ret.append(&mut error_site(MSMX_ERRCODE, span));
             ret.push(Instr::Jne(err_val));
         },
         // for equality, check2 must be Some(value2)
@@ -141,15 +672,27 @@ pub fn check_msmx(check : Val, check2_opn : Option<Val>, ctype : ValCheck, lbl :
                 *lbl += 1;
 
                 // Check if integer
-                ret.push(Instr::Mov(Val::Reg(Reg::RDX), check));
+                ret.push(Instr::Mov(Val::Reg(Reg::RDX), check.clone()));
                 ret.push(Instr::Test(Val::Reg(Reg::RDX), Val::Imm(1)));
                 ret.push(Instr::Je(int_chk.clone()));   // if integer, skip alt check
 
-                // Alt check
+                // Alt check: booleans and heap values have different low two bits
                 ret.push(Instr::Xor(Val::Reg(Reg::RDX), check2.clone()));
                 ret.push(Instr::And(Val::Reg(Reg::RDX), Val::Imm(0b11)));
                 ret.push(Instr::Cmp(Val::Reg(Reg::RDX), Val::Imm(0)));
-                ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Imm(MSMX_ERRCODE)));
+                ret.append(&mut error_site(MSMX_ERRCODE, span));
+                ret.push(Instr::Jne(err_val.clone()));
+
+                // Heap values of different kinds have different heap tags
+                ret.push(Instr::Mov(Val::Reg(Reg::RDX), check.clone()));
+                ret.push(Instr::And(Val::Reg(Reg::RDX), Val::Imm(0b11)));
+                ret.push(Instr::Cmp(Val::Reg(Reg::RDX), Val::Imm(0b11)));
+                ret.push(Instr::Je(chk_end.clone()));   // if boolean, skip heap tag check
+                ret.push(Instr::Mov(Val::Reg(Reg::RDX), check));
+                ret.push(Instr::Xor(Val::Reg(Reg::RDX), check2.clone()));
+                ret.push(Instr::And(Val::Reg(Reg::RDX), Val::Imm(HEAP_TAG_MASK)));
+                ret.push(Instr::Cmp(Val::Reg(Reg::RDX), Val::Imm(0)));
+                ret.append(&mut error_site(MSMX_ERRCODE, span));
                 ret.push(Instr::Jne(err_val.clone()));
                 ret.push(Instr::Jmp(chk_end.clone()));
 
@@ -157,7 +700,7 @@ pub fn check_msmx(check : Val, check2_opn : Option<Val>, ctype : ValCheck, lbl :
                 ret.push(Instr::Label(int_chk));
                 ret.push(Instr::Xor(Val::Reg(Reg::RDX), check2));
                 ret.push(Instr::Test(Val::Reg(Reg::RDX), Val::Imm(1)));
-                ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Imm(MSMX_ERRCODE)));
+                ret.append(&mut error_site(MSMX_ERRCODE, span));
                 ret.push(Instr::Jne(err_val));
                 
                 // End check
@@ -172,22 +715,32 @@ pub fn check_msmx(check : Val, check2_opn : Option<Val>, ctype : ValCheck, lbl :
 }
 
 /// Returns instructions that perform a runtime overflow error check
-pub fn check_of() -> Vec<Instr> {
+pub fn check_of(span : &Span) -> Vec<Instr> {
     let mut ret : Vec<Instr> = Vec::new();
-    let err_val = Val::Label(format!("throw_error_align"));
-    ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Imm(OF_ERRCODE)));
+    let err_val = Val::Label(String::from("throw_error_align"));
+    ret.append(&mut error_site(OF_ERRCODE, span));
     ret.push(Instr::Jo(err_val));
     ret
 }
 
+/// Returns instructions that report an overflow when the preceding comparison found a
+/// result that does not convert back to its operand (for operations that do not set OF)
+pub fn check_of_ne(span : &Span) -> Vec<Instr> {
+    let mut ret : Vec<Instr> = Vec::new();
+    let err_val = Val::Label(String::from("throw_error_align"));
+    ret.append(&mut error_site(OF_ERRCODE, span));
+    ret.push(Instr::Jne(err_val));
+    ret
+}
+
 /// Returns instructions that perform a runtime overflow error check
 // lower, inclusive determine the nature of the validity bound
 // e.g. lower = true, inclusive = true means >= bound is good, < bound is bad
-pub fn check_bnd(check: Val, bound: Val, lower : bool, inclusive: bool) -> Vec<Instr> {
+pub fn check_bnd(check: Val, bound: Val, lower : bool, inclusive: bool, span : &Span) -> Vec<Instr> {
     let mut ret : Vec<Instr> = Vec::new();
-    let err_val = Val::Label(format!("throw_error_align"));
+    let err_val = Val::Label(String::from("throw_error_align"));
     ret.push(Instr::Cmp(check, bound));
-    ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::Imm(BND_ERRCODE)));
+    ret.append(&mut error_site(BND_ERRCODE, span));
     ret.push(
         match (lower, inclusive) {
             (false, false) => Instr::Jge(err_val),
@@ -199,16 +752,105 @@ pub fn check_bnd(check: Val, bound: Val, lower : bool, inclusive: bool) -> Vec<I
     ret
 }
 
+/// Returns instructions that perform a runtime arity error check on a call of the closure
+/// whose untagged heap pointer is in rbx, with the number of arguments in rcx. The arity of a
+/// closure holds the least number of arguments it accepts in its low 32 bits and how many more it
+/// accepts in its high 32 bits, which is its number of optional parameters plus REST_ARITY if it
+/// has a rest parameter.
+pub fn check_arity(span : &Span) -> Vec<Instr> {
+    let mut ret : Vec<Instr> = Vec::new();
+    let err_val = Val::Label(String::from("throw_error_align"));
+    ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::MemPtr(Reg::RBX, 0)));
+    ret.push(Instr::Shl(Val::Reg(Reg::RDX), Val::Imm(32)));
+    ret.push(Instr::Sar(Val::Reg(Reg::RDX), Val::Imm(32)));
+    ret.push(Instr::Mov(Val::Reg(Reg::RAX), Val::Reg(Reg::RCX)));
+    ret.push(Instr::Sub(Val::Reg(Reg::RAX), Val::Reg(Reg::RDX)));
+    ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::MemPtr(Reg::RBX, 0)));
+    ret.push(Instr::Sar(Val::Reg(Reg::RDX), Val::Imm(32)));
+    // Fewer arguments than the least number make the difference negative, which is above any count unsigned
+    ret.push(Instr::Cmp(Val::Reg(Reg::RAX), Val::Reg(Reg::RDX)));
+    ret.append(&mut error_site(ARITY_ERRCODE, span));
+    ret.push(Instr::Ja(err_val));
+    ret
+}
+
+/// Returns instructions that move the number of parameters of the closure whose untagged heap
+/// pointer is in rbx into rax, counting its rest parameter, using rdx and rsi
+pub fn closure_nparams() -> Vec<Instr> {
+    vec![
+        Instr::Mov(Val::Reg(Reg::RAX), Val::MemPtr(Reg::RBX, 0)),
+        Instr::Shl(Val::Reg(Reg::RAX), Val::Imm(32)),
+        Instr::Sar(Val::Reg(Reg::RAX), Val::Imm(32)),
+        Instr::Mov(Val::Reg(Reg::RDX), Val::MemPtr(Reg::RBX, 0)),
+        Instr::Sar(Val::Reg(Reg::RDX), Val::Imm(32)),
+        Instr::Mov(Val::Reg(Reg::RSI), Val::Reg(Reg::RDX)),
+        Instr::And(Val::Reg(Reg::RSI), Val::Imm(REST_ARITY - 1)),
+        Instr::Add(Val::Reg(Reg::RAX), Val::Reg(Reg::RSI)),
+        Instr::Sar(Val::Reg(Reg::RDX), Val::Imm(REST_ARITY.trailing_zeros().into())),
+        Instr::Add(Val::Reg(Reg::RAX), Val::Reg(Reg::RDX)),
+    ]
+}
+
+/// Returns instructions that perform a runtime check that the global read into rax has been set
+pub fn check_global_set(span : &Span) -> Vec<Instr> {
+    let mut ret : Vec<Instr> = Vec::new();
+    let err_val = Val::Label(String::from("throw_error_align"));
+    ret.push(Instr::Cmp(Val::Reg(Reg::RAX), Val::Imm(UNSET_GLOBAL)));
+    ret.append(&mut error_site(GLOBAL_ERRCODE, span));
+    ret.push(Instr::Je(err_val));
+    ret
+}
+
+/// Returns instructions that perform a runtime division by zero check on a divisor
+pub fn check_div_zero(check : Val, span : &Span) -> Vec<Instr> {
+    let mut ret : Vec<Instr> = Vec::new();
+    let err_val = Val::Label(String::from("throw_error_align"));
+    ret.push(Instr::Cmp(check, Val::Imm(0)));
+    ret.append(&mut error_site(DIV_ERRCODE, span));
+    ret.push(Instr::Je(err_val));
+    ret
+}
+
+/// Returns instructions that check a shift count is not negative, as an invalid argument
+pub fn check_shift_count(check : Val, span : &Span) -> Vec<Instr> {
+    let mut ret : Vec<Instr> = Vec::new();
+    let err_val = Val::Label(String::from("throw_error_align"));
+    ret.push(Instr::Cmp(check, Val::Imm(0)));
+    ret.append(&mut error_site(MSMX_ERRCODE, span));
+    ret.push(Instr::Jl(err_val));
+    ret
+}
+
+/// Returns instructions that unconditionally stop the program with a runtime error
+pub fn throw_error(errcode : i64, span : &Span) -> Vec<Instr> {
+    let mut ret = error_site(errcode, span);
+    ret.push(Instr::Jmp(Val::Label(String::from("throw_error_align"))));
+    ret
+}
+
+/// Returns instructions that perform a runtime struct error check on the record in a register,
+/// comparing the descriptor it points to with the one of the expected struct
+pub fn check_struct(check : Reg, sid : StructId, span : &Span) -> Vec<Instr> {
+    let mut ret : Vec<Instr> = Vec::new();
+    let err_val = Val::Label(String::from("throw_error_align"));
+    ret.push(Instr::Mov(Val::Reg(Reg::RDX), Val::MemPtr(check, -RECORD_TAG as i32)));
+    ret.push(Instr::Lea(Val::Reg(Reg::RCX), Val::LabelAddr(format!("snek_struct_{sid}"))));
+    ret.push(Instr::Cmp(Val::Reg(Reg::RDX), Val::Reg(Reg::RCX)));
+    ret.append(&mut error_site(STRUCT_ERRCODE, span));
+    ret.push(Instr::Jne(err_val));
+    ret
+}
+
 /// Converts a vector of instructions to a String representation
 /// of the asm instruction list
-pub fn to_asm(instrs: &Vec<Instr>) -> String {
+pub fn to_asm(instrs: &[Instr]) -> String {
     let mut asm_str = String::new();
     if instrs.is_empty() {
         return asm_str;
     }
     // formats instructions with a new line after, except for last line
     for instr in &instrs[..instrs.len()-1] {
-        asm_str += &(String::from("  ") + &instr_to_str(&instr) + "\n");
+        asm_str += &(String::from("  ") + &instr_to_str(instr) + "\n");
     }
     asm_str += &(String::from("  ") + &instr_to_str(&instrs[instrs.len()-1]));
     asm_str
@@ -225,14 +867,17 @@ pub fn instr_to_str(i: &Instr) -> String {
             Instr::IMul(v1, v2) | Instr::CMovl(v1, v2) | Instr::CMovg(v1, v2) | 
             Instr::CMovle(v1, v2) | Instr::CMovge(v1, v2) | Instr::CMove(v1, v2) | 
             Instr::Cmp(v1, v2) | Instr::Test(v1, v2) | Instr::And(v1, v2) | 
-            Instr::Xor(v1, v2) | Instr::Sar(v1, v2)
+            Instr::Or(v1, v2) | Instr::Xor(v1, v2) | Instr::Sar(v1, v2) | Instr::Shl(v1, v2)
                 => format!("{}, {}", val_to_str(v1), val_to_str(v2)), // _, _
             Instr::Jmp(v) | Instr::Je(v) | Instr::Jne(v) | 
-            Instr::Jl(v) | Instr::Jle(v) | Instr::Jg(v) | Instr::Jge(v) |
-            Instr::Jo(v) | Instr::Push(v) | Instr::Pop(v) | Instr::Call(v)
-                => format!("{}", val_to_str(v)), // _
+            Instr::Jl(v) | Instr::Jle(v) | Instr::Jg(v) | Instr::Jge(v) | Instr::Ja(v) |
+            Instr::Jo(v) | Instr::Push(v) | Instr::Pop(v) | Instr::Call(v) |
+            Instr::IDiv(v) | Instr::Not(v)
+                => val_to_str(v), // _
+            Instr::Lea(v1, v2)
+                => format!("{}, {}", val_to_str(v1), val_to_str(v2)), // _, [rel _]
             Instr::Ret
-                => format!(""), // nothing
+                => String::new(), // nothing
             _ => String::from(""), // nothing
         })
     }
@@ -242,9 +887,7 @@ pub fn instr_to_str(i: &Instr) -> String {
 pub fn val_to_str(v: &Val) -> String {
     match v {
         Val::Reg(reg) => reg.to_string().to_lowercase(),  // register name
-        Val::Imm(imm) => {
-            return imm.to_string();        // immediate integer to string
-        },
+        Val::Imm(imm) => imm.to_string(),   // immediate integer to string
         Val::MemPtr(reg, imm) => {      // qword [reg +- imm]
             if *imm < 0 {
                 format!("qword [{} - {}]", reg.to_string().to_lowercase(), -*imm)
@@ -255,14 +898,25 @@ pub fn val_to_str(v: &Val) -> String {
             }
         },
         Val::Label(s) => s.to_string(),    // label
+        Val::LabelAddr(s) => format!("[rel {s}]"),  // [rel label]
     }
 }
 
+/// Returns whether a name has the shape of an operation generated by a struct declaration:
+/// a predicate (name?), an accessor (name-field) or a mutator (set-name-field!)
+pub fn is_struct_op_name(s: &str) -> bool {
+    if let Some(name) = s.strip_suffix('?') {
+        return is_valid_identifier(name);
+    }
+    let s = s.strip_prefix("set-").and_then(|s| s.strip_suffix('!')).unwrap_or(s);
+    matches!(s.split_once('-'), Some((name, field)) if is_valid_identifier(name) && is_valid_identifier(field))
+}
+
 /// Returns whether or not an identifier or function name is valid
 /// (of the pattern [a-zA-z][a-zA-Z0-9]*)
 pub fn is_valid_identifier(s: &str) -> bool {
     // check if first character is alphabetic
-    if s.len() <= 0 || !s.chars().nth(0).unwrap().is_ascii_alphabetic() {
+    if s.is_empty() || !s.chars().next().unwrap().is_ascii_alphabetic() {
         return false;
     }
     // check if all other characters are alphanumeric
@@ -277,26 +931,43 @@ pub fn is_valid_identifier(s: &str) -> bool {
 /// Gets the maximum function arity for an expression
 #[allow(dead_code)]
 pub fn get_max_func_arity(expr: Expr, max_arity: i32) -> i32 {
-    match expr {
-        Expr::Let(_, e) => {
+    match expr.kind {
+        ExprKind::Let(_, e) => {
             get_max_func_arity(*e, max_arity)
         },
-        Expr::If(_, e_then, e_else) => {
+        ExprKind::If(_, e_then, e_else) => {
             max(get_max_func_arity(*e_then, max_arity), get_max_func_arity(*e_else, max_arity))
         },
-        Expr::Loop(e) => {
+        ExprKind::Loop(e) => {
             get_max_func_arity(*e, max_arity)
         },
-        Expr::Break(e) => {
+        ExprKind::Break(e) => {
             get_max_func_arity(*e, max_arity)
         },
-        Expr::Block(list_e) => {
+        ExprKind::Block(list_e) => {
             let e = &list_e[list_e.len()-1];
             get_max_func_arity(e.clone(), max_arity)
         },
-        Expr::Call(_, list_args) => {
+        ExprKind::Call(_, list_args) => {
             max(max_arity, list_args.len().try_into().unwrap())
         },
         _ => max_arity
     }
-}
\ No newline at end of file
+}
+
+impl RStruct {
+    /// Name of the type predicate
+    pub fn predicate(&self) -> String {
+        format!("{}?", self.name)
+    }
+
+    /// Name of the accessor of the field at the given position
+    pub fn accessor(&self, field: usize) -> String {
+        format!("{}-{}", self.name, self.fields[field])
+    }
+
+    /// Name of the mutator of the field at the given position
+    pub fn mutator(&self, field: usize) -> String {
+        format!("set-{}-{}!", self.name, self.fields[field])
+    }
+}
@@ -66,14 +66,46 @@ success_tests! {
         input: "9",
         expected: "9\nfalse\nfalse",
     },
+    {
+        name: comments,
+        file: "main/comments.snek",
+        expected: "10\n12",
+    },
+    {
+        name: resolve_shadowed_set,
+        file: "main/resolve_shadowed_set.snek",
+        expected: "11\n1",
+    },
+    {
+        name: points,
+        file: "main/points.snek",
+        expected: "(13, -51)\n(79, 24)\n(14, -32)\n(92, -27)\n(27, -83)\n(93, -8)\n(106, -59)",
+    },
+    {
+        name: bst,
+        file: "main/bst.snek",
+        expected: "(10, 4, 13, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false)
+(10, 4, 13, -3, 6, 12, false, false, false, false, false, false, false, false, false, false, false, false, false, false)
+false
+true
+true
+true
+(10, 4, 13, -3, 6, 12, false, false, false, false, false, false, false, false, false, false, false, false, false, false)",
+    },
 }
 
 runtime_error_tests! {
     {
         name: ia_fun_list,
         file: "main/fun_list.snek",
-        expected: "invalid argument",
-    }
+        expected: "R0001",
+    },
+    {
+        name: error_location,
+        file: "main/error_location.snek",
+        flags: ["-W", "definite-failure"],
+        expected: "main/error_location.snek:5:5",
+    },
 }
 
 static_error_tests! {
@@ -81,7 +113,939 @@ static_error_tests! {
     {
         name: duplicate_params,
         file: "main/duplicate_params.snek",
-        expected: "",
+        expected: "E0011",
+    },
+
+    // Tuple operations cannot be redefined as functions
+    {
+        name: tuple_function_name,
+        file: "main/tuple_function_name.snek",
+        expected: "E0009",
+    },
+
+    // Unterminated block comment
+    {
+        name: comment_unterminated,
+        file: "main/comment_unterminated.snek",
+        expected: "E0001",
+    },
+
+    // Independent errors are all reported by a single compile
+    {
+        name: multiple_errors_binding,
+        file: "main/multiple_errors.snek",
+        expected: "E0012",
+    },
+    {
+        name: multiple_errors_arity,
+        file: "main/multiple_errors.snek",
+        expected: "E0015",
+    },
+    {
+        name: multiple_errors_function,
+        file: "main/multiple_errors.snek",
+        expected: "E0010",
+    },
+    {
+        name: multiple_errors_unknown,
+        file: "main/multiple_errors.snek",
+        expected: "E0014",
+    },
+    {
+        name: multiple_errors_let,
+        file: "main/multiple_errors.snek",
+        expected: "E0005",
+    },
+    {
+        name: multiple_errors_unbound,
+        file: "main/multiple_errors.snek",
+        expected: "E0013",
+    },
+}
+
+// A call to a function with an invalid name adds no diagnostic of its own
+snippet_tests! {
+    {
+        name: invalid_function_name_calls,
+        file: "main/invalid_function_name.snek",
+        expected: "aborting due to 2 previous error(s)",
+    },
+}
+
+// Lint warnings, which can be allowed or denied from the command line
+warning_tests! {
+    {
+        name: lint_unused_binding,
+        file: "main/lints.snek",
+        expected: "W0001",
+    },
+    {
+        name: lint_unused_parameter,
+        file: "main/lints.snek",
+        expected: "W0002",
+    },
+    {
+        name: lint_unused_function,
+        file: "main/lints.snek",
+        expected: "W0003",
+    },
+    {
+        name: lint_shadowed_binding,
+        file: "main/lints.snek",
+        expected: "W0004",
+    },
+    {
+        name: lint_many_unused_functions,
+        file: "course/diamondback_many_unused_functions.snek",
+        expected: "W0003",
+    },
+    {
+        name: lint_boa_shadowed_binding,
+        file: "course/boa_shadowed_binding_succ0.snek",
+        expected: "W0004",
+    },
+    {
+        name: lint_warn_flag,
+        file: "main/lints.snek",
+        flags: ["-A", "unused-function", "-Wunused-function"],
+        expected: "W0003",
+    },
+}
+
+success_tests! {
+    {
+        name: lint_allow_all,
+        file: "main/lints.snek",
+        flags: ["-A", "unused-binding", "-A", "unused-parameter", "-AW0003", "-Ashadowed-binding"],
+        expected: "2",
+    },
+}
+
+static_error_tests! {
+    {
+        name: lint_deny_unused_function,
+        file: "course/diamondback_many_unused_functions.snek",
+        flags: ["-D", "unused-function"],
+        expected: "W0003",
+    },
+    {
+        name: lint_deny_shadowed_binding,
+        file: "main/lints.snek",
+        flags: ["-Dshadowed-binding"],
+        expected: "W0004",
+    },
+}
+
+// Operations that always fail their runtime type check, denied by default
+static_error_tests! {
+    {
+        name: definite_failure_add1,
+        file: "course/cobra_invalid_argument_fail0.snek",
+        expected: "W0005",
+    },
+    {
+        name: definite_failure_all_paths,
+        file: "main/definite_failure.snek",
+        expected: "W0005",
+    },
+    {
+        name: definite_failure_call,
+        file: "main/definite_failure_call.snek",
+        expected: "W0005",
+    },
+}
+
+warning_tests! {
+    {
+        name: definite_failure_permissive,
+        file: "main/definite_failure.snek",
+        flags: ["-W", "definite-failure"],
+        expected: "W0005",
+    },
+}
+
+success_tests! {
+    {
+        name: definite_failure_some_paths,
+        file: "main/definite_failure_maybe.snek",
+        input: "1",
+        expected: "42",
+    },
+    {
+        name: definite_failure_uncalled,
+        file: "main/definite_failure_uncalled.snek",
+        expected: "5",
+    },
+    {
+        name: definite_failure_call_some_paths,
+        file: "main/definite_failure_call_maybe.snek",
+        input: "false",
+        expected: "1",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: definite_failure_some_paths_fail,
+        file: "main/definite_failure_maybe.snek",
+        input: "false",
+        expected: "R0001",
+    },
+    {
+        name: definite_failure_call_some_paths_fail,
+        file: "main/definite_failure_call_maybe.snek",
+        input: "true",
+        expected: "R0001",
+    },
+}
+
+// Lambdas, closures and functions used as values
+success_tests! {
+    {
+        name: closures,
+        file: "main/closures.snek",
+        input: "5",
+        expected: "21\n2\n<function>\n20",
+    },
+    {
+        name: closures_nested,
+        file: "main/closures_nested.snek",
+        expected: "101\n321",
+    },
+    {
+        name: closures_tail_call,
+        file: "main/closures_tail.snek",
+        input: "10000000",
+        expected: "10000000",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: closure_arity_mismatch,
+        file: "main/closure_arity_fail.snek",
+        input: "1",
+        expected: "R0004",
+    },
+    {
+        name: closure_call_non_function,
+        file: "main/closure_call_fail.snek",
+        input: "1",
+        expected: "R0001",
+    },
+}
+
+static_error_tests! {
+    {
+        name: closure_call_number,
+        file: "main/closure_call_number.snek",
+        expected: "W0005",
+    },
+    {
+        name: closure_set_captured,
+        file: "main/closure_set_captured.snek",
+        expected: "E0021",
+    },
+    {
+        name: closure_set_outer,
+        file: "main/closure_set_outer.snek",
+        expected: "E0021",
+    },
+    {
+        name: lambda_invalid_form,
+        file: "main/lambda_invalid.snek",
+        expected: "E0005",
+    },
+}
+
+// String literals and string operations
+success_tests! {
+    {
+        name: strings,
+        file: "main/strings.snek",
+        input: "5",
+        expected: "hello, world\nsay \"hi\"\tnow\\\n13\nworld\ntrue\nfalse\ntrue\n(a, 1, ababab)\n-42\nfalse\n0\n5!",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: substring_out_of_bounds,
+        file: "main/substring_bounds_fail.snek",
+        input: "5",
+        expected: "R0003",
+    },
+    {
+        name: string_length_invalid_argument,
+        file: "main/string_length_fail.snek",
+        input: "5",
+        expected: "R0001",
+    },
+}
+
+static_error_tests! {
+    {
+        name: string_append_definite_failure,
+        file: "main/string_append_fail.snek",
+        expected: "W0005",
+    },
+}
+
+// Local function groups
+success_tests! {
+    {
+        name: letrec_functions,
+        file: "main/letrec.snek",
+        input: "5",
+        expected: "(true, true, false)\n(11, 12)\n55\n3628800\ntrue",
+    },
+    {
+        name: letrec_functions_even,
+        file: "main/letrec.snek",
+        input: "4",
+        expected: "(true, true, true)\n(11, 12)\n55\n3628800\ntrue",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: letrec_arity_mismatch,
+        file: "main/letrec_arity_fail.snek",
+        input: "5",
+        expected: "R0004",
+    },
+}
+
+static_error_tests! {
+    {
+        name: letrec_duplicate_function,
+        file: "main/letrec_duplicate.snek",
+        expected: "E0010",
+    },
+    {
+        name: letrec_invalid,
+        file: "main/letrec_invalid.snek",
+        expected: "E0005",
+    },
+}
+
+// Top-level globals and input
+success_tests! {
+    {
+        name: globals,
+        file: "main/globals.snek",
+        input: "2",
+        expected: "6\n20\nb\n0\n3",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: global_read_before_initializer,
+        file: "main/global_unset.snek",
+        expected: "R0008",
+    },
+}
+
+static_error_tests! {
+    {
+        name: global_function_clash,
+        file: "main/global_duplicate.snek",
+        expected: "E0020",
+    },
+    {
+        name: global_used_before_definition,
+        file: "main/global_forward.snek",
+        expected: "E0013",
+    },
+    {
+        name: global_set_input,
+        file: "main/global_set_input.snek",
+        expected: "E0009",
+    },
+    {
+        name: global_not_top_level,
+        file: "main/global_nested.snek",
+        expected: "E0005",
+    },
+}
+
+// Exceptions
+success_tests! {
+    {
+        name: exceptions,
+        file: "main/exceptions.snek",
+        input: "2",
+        expected: "5\n-3\n(negative, -8)\nR0001\nR0002\nR0003\n3\nouter R0001\n3\n20\ndone",
+    },
+    {
+        name: exceptions_no_errors,
+        file: "main/exceptions.snek",
+        input: "0",
+        expected: "5\n-3\n(negative, -10)\nR0001\n4611686018427387903\n1\n0\nouter R0001\n3\n20\ndone",
+    },
+    {
+        name: raise_caught_by_nothing_not_reached,
+        file: "main/raise_uncaught.snek",
+        input: "20",
+        expected: "10",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: raise_uncaught,
+        file: "main/raise_uncaught.snek",
+        input: "3",
+        expected: "R0009]: uncaught exception: negative",
+    },
+}
+
+static_error_tests! {
+    {
+        name: try_without_catch,
+        file: "main/try_invalid.snek",
+        expected: "E0005",
+    },
+}
+
+// Assertions and panics
+success_tests! {
+    {
+        name: assert_holds,
+        file: "main/assert.snek",
+        input: "3",
+        expected: "true\n6",
+    },
+    {
+        name: assert_release,
+        file: "main/assert.snek",
+        flags: ["--release"],
+        input: "12",
+        expected: "true\n479001600",
+    },
+    {
+        name: assert_release_definite_failure,
+        file: "main/assert_definite_failure.snek",
+        flags: ["--release"],
+        expected: "5",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: assert_fails,
+        file: "main/assert.snek",
+        input: "12",
+        expected: "panic: assertion failed\n  --> tests/main/assert.snek:7:10\n  in (assert (< input 10))",
+    },
+    {
+        name: assert_fails_with_message,
+        file: "main/assert.snek",
+        input: "-2",
+        expected: "panic: negative argument -2",
+    },
+    {
+        name: panic_with_value,
+        file: "main/panic.snek",
+        input: "5",
+        expected: "panic: (missing, 5)\n  --> tests/main/panic.snek:2:33\n  in (panic (tuple \"missing\" i))",
+    },
+}
+
+static_error_tests! {
+    {
+        name: assert_too_many_arguments,
+        file: "main/assert_invalid.snek",
+        expected: "E0005",
+    },
+    {
+        name: assert_definite_failure,
+        file: "main/assert_definite_failure.snek",
+        expected: "W0005",
+    },
+}
+
+// Variadic functions and apply
+success_tests! {
+    {
+        name: variadic,
+        file: "main/variadic.snek",
+        input: "4",
+        expected: "0\n10\n(x, 0, ())\n(y, 2, (1, true))\n14\n15\n(z, 1, (4))\n8\n21\n5\n4\n5\n10\nR0004",
+    },
+    {
+        name: apply_variadic,
+        file: "main/apply_arity.snek",
+        input: "3",
+        expected: "(1, 2, (3))",
+    },
+    {
+        name: variadic_deep_tail_calls,
+        file: "main/variadic_deep.snek",
+        input: "450000",
+        expected: "(1)",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: apply_too_few_arguments,
+        file: "main/apply_arity.snek",
+        input: "1",
+        expected: "R0004",
+    },
+}
+
+static_error_tests! {
+    {
+        name: variadic_too_few_arguments,
+        file: "main/variadic_too_few.snek",
+        expected: "E0015",
+    },
+    {
+        name: variadic_rest_not_last,
+        file: "main/variadic_invalid.snek",
+        expected: "E0008",
+    },
+}
+
+// Optional parameters with default values
+success_tests! {
+    {
+        name: optional,
+        file: "main/optional.snek",
+        input: "100000",
+        expected: "(2, 10, 20)\n(2, 3, 6)\n(2, 3, 4)\ntrue\nfalse\n100000\n(1, false, ())\n(1, 2, (3, 4))\n(5, 10, 50)\n(1, 2, 2)\n(1, false, ())\n(1, 2, (3))\n100001\n2\n100007\n1000",
+    },
+    {
+        name: optional_deep_tail_calls,
+        file: "main/optional_deep.snek",
+        input: "1000000",
+        expected: "500000500000\n55\n500000500000",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: optional_too_many_arguments_at_runtime,
+        file: "main/optional_arity.snek",
+        input: "1",
+        expected: "R0004",
+    },
+}
+
+static_error_tests! {
+    {
+        name: optional_too_many_arguments,
+        file: "main/optional_too_many.snek",
+        expected: "E0015",
+    },
+    {
+        name: optional_before_required,
+        file: "main/optional_invalid.snek",
+        expected: "E0004",
+    },
+}
+
+// Command-line arguments
+success_tests! {
+    {
+        name: args_several,
+        file: "main/args.snek",
+        args: ["3", "4", "5"],
+        expected: "3\n3\n12\n5",
+    },
+    {
+        name: args_one,
+        file: "main/args.snek",
+        input: "7",
+        expected: "1\n7\n7\n7",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: args_none,
+        file: "main/args.snek",
+        expected: "R0003",
+    },
+}
+
+static_error_tests! {
+    {
+        name: argc_with_argument,
+        file: "main/argc_invalid.snek",
+        expected: "E0015",
+    },
+}
+
+// Struct declarations and records
+success_tests! {
+    {
+        name: structs,
+        file: "main/structs.snek",
+        input: "5",
+        expected: "point{x: 1, y: 2}\ntrue\nfalse\nfalse\ntrue\npoint{x: 11, y: 22}\ntrue\n(empty{}, size{x: point{x: 4, y: 2}, y: 4})\n9",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: struct_accessor_wrong_struct,
+        file: "main/struct_mismatch_fail.snek",
+        input: "5",
+        expected: "R0005",
+    },
+    {
+        name: struct_accessor_not_record,
+        file: "main/struct_get_fail.snek",
+        input: "5",
+        expected: "R0001",
+    },
+}
+
+static_error_tests! {
+    {
+        name: struct_duplicate_field,
+        file: "main/struct_duplicate_field.snek",
+        expected: "E0018",
+    },
+    {
+        name: struct_invalid_form,
+        file: "main/struct_invalid.snek",
+        expected: "E0005",
+    },
+    {
+        name: struct_operation_name_clash,
+        file: "main/struct_name_clash.snek",
+        expected: "E0010",
+    },
+}
+
+// Match expressions and patterns
+success_tests! {
+    {
+        name: match_patterns,
+        file: "main/match.snek",
+        input: "5",
+        expected: "((false, 3, false), 5, (false, 8, (false, 9, false)))\n25\n1\n1000000\n(1000, 2000, 6, 6, 7)\n10",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: match_no_case,
+        file: "main/match_fail.snek",
+        input: "5",
+        expected: "R0006",
+    },
+}
+
+static_error_tests! {
+    {
+        name: match_invalid_pattern,
+        file: "main/match_invalid_pattern.snek",
+        expected: "E0019",
+    },
+    {
+        name: match_duplicate_binding,
+        file: "main/match_duplicate_binding.snek",
+        expected: "E0012",
+    },
+}
+
+// Boolean connectives and cond
+success_tests! {
+    {
+        name: logic,
+        file: "main/logic.snek",
+        input: "5",
+        expected: "(true, false, false, false, true, true)\n(false, true, false)\nfalse\ntrue\ntrue\ntrue\n(-1, 0, 1)\n111\n0\ntrue\n7\n5",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: and_invalid_argument,
+        file: "main/logic_and_fail.snek",
+        input: "5",
+        expected: "R0001",
+    },
+}
+
+static_error_tests! {
+    {
+        name: not_definite_failure,
+        file: "main/logic_not_fail.snek",
+        expected: "W0005",
+    },
+    {
+        name: cond_without_else,
+        file: "main/cond_invalid.snek",
+        expected: "E0005",
+    },
+}
+
+// Tuple introspection and bulk operations
+success_tests! {
+    {
+        name: tuple_operations,
+        file: "main/tuple_ops.snek",
+        input: "5",
+        expected: "(5, 0, 15)\n(true, false, false, false)\n(1, 2, 3, 4, 5)\n(100, 2, 3, 4, 5)\n(2, 3)\n()\n(1, 2, 3, 4, 5, 6, 7)\n()\n10",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: tslice_out_of_bounds,
+        file: "main/tslice_bounds_fail.snek",
+        input: "4",
+        expected: "R0003",
+    },
+    {
+        name: tslice_reversed_bounds,
+        file: "main/tslice_bounds_fail.snek",
+        input: "0",
+        expected: "R0003",
+    },
+    {
+        name: tappend_invalid_argument,
+        file: "main/tappend_fail.snek",
+        input: "5",
+        expected: "R0001",
+    },
+}
+
+// Structural equality
+success_tests! {
+    {
+        name: structural_equality,
+        file: "main/equal.snek",
+        input: "5",
+        expected: "(false, true, true)\n(true, false, true, false, false)\n(false, true, true)\n(true, false, false)\n(true, false)\ntrue\nfalse",
+    },
+    {
+        name: structural_equality_cyclic,
+        file: "main/equal.snek",
+        input: "1",
+        expected: "(false, true, true)\n(true, false, true, false, false)\n(false, true, true)\n(true, false, false)\n(true, false)\ntrue\ntrue",
+    },
+}
+
+// Division, remainder and bitwise operators
+success_tests! {
+    {
+        name: division_and_bitwise,
+        file: "main/division.snek",
+        input: "5",
+        expected: "(3, -4, -4, 3, 2)\n(3, -3, -3, 3)\n(1, -1, 1, -1)\n(1, 1, -1, -1, 0)\n(8, 14, 6, 5, -1, -6, 5)\n(1024, -12, 0, 128, -4, -1, 0)\n-4611686018427387904\n2305843009213693952\n71",
+    },
+}
+
+runtime_error_tests! {
+    {
+        name: division_by_zero,
+        file: "main/division_zero_fail.snek",
+        input: "5",
+        expected: "R0007",
+    },
+    {
+        name: division_overflow,
+        file: "main/division_overflow_fail.snek",
+        input: "5",
+        expected: "R0002",
+    },
+    {
+        name: shift_left_overflow,
+        file: "main/shift_overflow_fail.snek",
+        input: "5",
+        expected: "R0002",
+    },
+    {
+        name: shift_negative_count,
+        file: "main/shift_negative_fail.snek",
+        input: "5",
+        expected: "R0001",
+    },
+}
+
+snippet_tests! {
+    {
+        name: string_unterminated,
+        file: "main/string_unterminated.snek",
+        expected: "error[E0001]: invalid s-expression: unterminated string literal\n --> tests/main/string_unterminated.snek:1:8",
+    },
+    {
+        name: string_bad_escape,
+        file: "main/string_bad_escape.snek",
+        expected: "1 | (print \"bad \\q escape\")\n  |             ^^\n",
+    },
+}
+
+// Diagnostics in --error-format=json mode
+json_error_tests! {
+    {
+        name: json_error_code,
+        file: "main/multiple_errors.snek",
+        expected: r#""code":"E0012","name":"duplicate-binding","severity":"error","message":"duplicate binding 'root'""#,
+    },
+    {
+        name: json_error_span,
+        file: "main/multiple_errors.snek",
+        expected: r#""start":272,"end":276,"line":13,"column":5,"end_line":13,"end_column":9"#,
+    },
+    {
+        name: json_error_help,
+        file: "main/multiple_errors.snek",
+        expected: r#""help":"rename one of the bindings or use a nested let""#,
+    },
+    {
+        name: json_error_denied_lint,
+        file: "main/lints.snek",
+        flags: ["-D", "unused-parameter"],
+        expected: r#""code":"W0002","name":"unused-parameter","severity":"error""#,
+    },
+    {
+        name: json_warning_with_label,
+        file: "main/lints.snek",
+        flags: ["-D", "unused-parameter"],
+        expected: r#""code":"W0004","name":"shadowed-binding","severity":"warning","message":"'a' shadows an earlier binding""#,
+    },
+    {
+        name: json_error_label,
+        file: "main/multiple_errors.snek",
+        expected: r#""labels":[{"span":{"file":"tests/main/multiple_errors.snek","start":87,"end":91,"line":4,"column":14,"end_line":4,"end_column":18},"message":"first bound here"}]"#,
+    },
+    {
+        name: json_error_reader,
+        file: "main/comment_unterminated.snek",
+        expected: r#""message":"invalid s-expression: unterminated block comment""#,
+    },
+}
+
+// Annotated source snippets in human-readable diagnostics
+snippet_tests! {
+    {
+        name: snippet_label_inside_primary,
+        file: "main/error_location.snek",
+        expected: "
+5 |     (+ x y)))
+  |     ^^^^^-^
+  |          |
+  |          this is always a boolean
+",
+    },
+    {
+        name: snippet_duplicate_binding,
+        file: "main/multiple_errors.snek",
+        expected: "
+4 |       (let ((root (tget node 0)) (root (tget node 1)))
+  |              ----                 ^^^^
+  |              |
+  |              first bound here
+",
+    },
+    {
+        name: snippet_duplicate_parameter,
+        file: "main/duplicate_params.snek",
+        expected: "
+1 | (fun (fun1 a a)
+  |            - ^
+  |            |
+  |            first bound here
+",
+    },
+    {
+        name: snippet_label_on_other_line,
+        file: "main/multiple_errors.snek",
+        expected: "
+1 | (fun (insert node val)
+  |       ------ first defined here
+...
+7 | (fun (insert node) node)
+  |       ^^^^^^
+",
+    },
+    {
+        name: snippet_wide_gutter_and_help,
+        file: "main/multiple_errors.snek",
+        expected: "
+  --> tests/main/multiple_errors.snek:12:5
+   |
+12 |     (let () tree)
+   |     ^^^^^^^^^^^^^
+   |
+   = help: let is written (let ((name expr) ...) body)
+",
+    },
+    {
+        name: snippet_tab_expansion,
+        file: "main/snippet_tab.snek",
+        expected: "
+2 |     (+ x    y))
+  |             ^
+",
+    },
+}
+
+// Long-form explanations of error and lint codes
+explain_tests! {
+    {
+        name: explain_static_error,
+        code: "E0012",
+        expected: "E0012 duplicate-binding",
+    },
+    {
+        name: explain_failing_example,
+        code: "E0012",
+        expected: "Erroneous example:\n\n    (let ((x 1) (x 2)) x)",
+    },
+    {
+        name: explain_fixed_example,
+        code: "E0012",
+        expected: "Fixed example:\n\n    (let ((x 1) (y 2)) (+ x y))",
+    },
+    {
+        name: explain_by_name,
+        code: "index-out-of-bounds",
+        expected: "R0003 index-out-of-bounds",
+    },
+    {
+        name: explain_lint,
+        code: "W0003",
+        expected: "-W/-A/-D unused-function",
+    },
+    {
+        name: explain_definite_failure,
+        code: "definite-failure",
+        expected: "W0005 definite-failure",
+    },
+    {
+        name: explain_retired_code,
+        code: "E0016",
+        expected: "E0016 input-in-function",
+    },
+    {
+        name: explain_captured_assignment,
+        code: "E0021",
+        expected: "E0021 captured-assignment",
+    },
+    {
+        name: explain_arity_mismatch,
+        code: "R0004",
+        expected: "(let ((add (lambda (x y) (+ x y)))) (add 1 2))",
+    },
+    {
+        name: explain_match_failure,
+        code: "R0006",
+        expected: "(match (tuple 1 2) ((tuple x) x) (_ 0))",
+    },
+    {
+        name: explain_struct_mismatch,
+        code: "struct-mismatch",
+        expected: "R0005 struct-mismatch",
     },
 }
 
@@ -138,6 +1102,11 @@ success_tests! {
     },
 
     // Nested Arithmetic Expressions
+    {
+        name: boa_parse_token3_division,
+        file: "course/boa_parse_token_fail3.snek",
+        expected: "2",
+    },
     {
         name: boa_nested_arith0,
         file: "course/boa_nested_arith0.snek",
@@ -440,9 +1409,10 @@ success_tests! {
         expected: "84",
     },
     {
-        name: diamondback_namespaces,
-        file: "course/diamondback_namespaces.snek",
-        expected: "2"
+        name: diamondback_fun_input1,
+        file: "course/diamondback_fun_input_fail1.snek",
+        input: "12",
+        expected: "12",
     },
 
     // More complex recursive functions
@@ -468,95 +1438,120 @@ runtime_error_tests! {
     {
         name: cobra_number_overflow_fail0,
         file: "course/cobra_number_overflow_fail0.snek",
-        expected: "overflow",
+        expected: "R0002",
     },
     {
         name: cobra_number_overflow_fail1,
         file: "course/cobra_number_overflow_fail1.snek",
-        expected: "overflow",
+        expected: "R0002",
     },
     {
         name: cobra_number_overflow_fail2,
         file: "course/cobra_add.snek",
         input: "4611686018427387899",
-        expected: "overflow",
+        expected: "R0002",
     },
     {
         name: cobra_number_overflow_fail3,
         file: "course/cobra_nested_arith3.snek",
         input: "4611686018427387890",
-        expected: "overflow",
+        expected: "R0002",
     },
     {
         name: diamondback_eventually_overflows,
         file: "course/diamondback_eventually_overflows.snek",
-        expected: "overflow",
+        expected: "R0002",
     },
 
     // type mismatch
     {
         name: cobra_invalid_argument_fail0,
         file: "course/cobra_invalid_argument_fail0.snek",
-        expected: "invalid argument",
+        flags: ["-W", "definite-failure"],
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail1,
         file: "course/cobra_invalid_argument_fail1.snek",
-        expected: "invalid argument",
+        flags: ["-W", "definite-failure"],
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail2,
         file: "course/cobra_invalid_argument_fail2.snek",
-        expected: "invalid argument",
+        flags: ["-W", "definite-failure"],
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail3,
         file: "course/cobra_invalid_argument_fail3.snek",
-        expected: "invalid argument",
+        flags: ["-W", "definite-failure"],
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail4,
         file: "course/cobra_invalid_argument_fail4.snek",
-        expected: "invalid argument",
+        flags: ["-W", "definite-failure"],
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail5,
         file: "course/cobra_invalid_argument_fail5.snek",
-        expected: "invalid argument",
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail6,
         file: "course/cobra_invalid_argument_fail6.snek",
-        expected: "invalid argument",
+        flags: ["-W", "definite-failure"],
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail7,
         file: "course/cobra_nested_arith3.snek",
         input: "true",
-        expected: "invalid argument",
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail8,
         file: "course/cobra_if_expr_input.snek",
         input: "665",
-        expected: "invalid argument",
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail9,
         file: "course/cobra_set_expr3.snek",
         input: "true",
-        expected: "invalid argument",
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail10,
         file: "course/cobra_loop_expr0.snek",
         input: "5",
-        expected: "invalid argument",
+        expected: "R0001",
     },
     {
         name: cobra_invalid_argument_fail11,
         file: "course/cobra_invalid_argument_fail11.snek",
-        expected: "invalid argument",
+        flags: ["-W", "definite-failure"],
+        expected: "R0001",
+    },
+
+    // Function names are values, and variables hide functions of the same name
+    {
+        name: diamondback_fun_scope_fail1,
+        file: "course/diamondback_fun_scope_fail1.snek",
+        expected: "R0001",
+    },
+    {
+        name: diamondback_fun_scope_fail2,
+        file: "course/diamondback_fun_scope_fail2.snek",
+        expected: "R0001",
+    },
+    {
+        name: diamondback_namespaces,
+        file: "course/diamondback_namespaces.snek",
+        flags: ["-W", "definite-failure"],
+        expected: "R0001",
     },
 }
 
@@ -566,34 +1561,29 @@ static_error_tests! {
     {
         name: boa_parse_sexp_fail1,
         file: "course/boa_parse_sexp_fail1.snek",
-        expected: "Invalid",
+        expected: "E0001",
     },
     {
         name: boa_parse_sexp_fail2,
         file: "course/boa_parse_sexp_fail2.snek",
-        expected: "Invalid",
+        expected: "E0001",
     },
 
     // Invalid tokens/operators
     {
         name: boa_parse_token_fail1,
         file: "course/boa_parse_token_fail1.snek",
-        expected: "",
+        expected: "E0014",
     },
     {
         name: boa_parse_token_fail2,
         file: "course/boa_parse_token_fail2.snek",
-        expected: "",
-    },
-    {
-        name: boa_parse_token_fail3,
-        file: "course/boa_parse_token_fail3.snek",
-        expected: "",
+        expected: "E0014",
     },
     {
         name: boa_parse_token_fail4,
         file: "course/boa_parse_token_fail4.snek",
-        expected: "",
+        expected: "E0005",
     },
 
 
@@ -601,276 +1591,261 @@ static_error_tests! {
     {
         name: cobra_number_bounds_fail0,
         file: "course/cobra_number_bounds_fail0.snek",
-        expected: "Invalid",
+        expected: "E0007",
     },
     {
         name: cobra_number_bounds_fail1,
         file: "course/cobra_number_bounds_fail1.snek",
-        expected: "Invalid",
+        expected: "E0007",
     },
 
     // Invalid operator arguments
     {
         name: boa_parse_op_fail1,
         file: "course/boa_parse_op_fail1.snek",
-        expected: "Invalid",
+        expected: "E0015",
     },
     {
         name: boa_parse_op_fail2,
         file: "course/boa_parse_op_fail2.snek",
-        expected: "Invalid",
+        expected: "E0015",
     },
     {
         name: boa_parse_op_fail3,
         file: "course/boa_parse_op_fail3.snek",
-        expected: "Invalid",
+        expected: "E0015",
     },
     {
         name: boa_parse_op_fai4,
         file: "course/boa_parse_op_fail4.snek",
-        expected: "Invalid",
+        expected: "E0015",
     },
     {
         name: boa_parse_op_fail5,
         file: "course/boa_parse_op_fail5.snek",
-        expected: "",
+        expected: "E0015",
     },
     {
         name: cobra_parse_op_fail6,
         file: "course/cobra_parse_op_fail6.snek",
-        expected: "Invalid",
+        expected: "E0015",
     },
     {
         name: cobra_parse_op_fail7,
         file: "course/cobra_parse_op_fail7.snek",
-        expected: "Invalid",
+        expected: "E0015",
     },
     {
         name: cobra_parse_op_fail8,
         file: "course/cobra_parse_op_fail8.snek",
-        expected: "Invalid",
+        expected: "E0015",
     },
 
     // Invalid let expressions
     {
         name: boa_parse_let_nobindings_fail,
         file: "course/boa_parse_let_nobindings_fail.snek",
-        expected: "Invalid",
+        expected: "E0005",
     },
     {
         name: boa_parse_let_improperargs_fail1,
         file: "course/boa_parse_let_improperargs_fail1.snek",
-        expected: "Invalid",
+        expected: "E0005",
     },
     {
         name: boa_parse_let_improperargs_fail2,
         file: "course/boa_parse_let_improperargs_fail2.snek",
-        expected: "Invalid",
+        expected: "E0005",
     },
     {
         name: boa_parse_let_improperargs_fail3,
         file: "course/boa_parse_let_improperargs_fail3.snek",
-        expected: "",
+        expected: "E0014",
     },
     {
         name: boa_parse_let_improperargs_fail4,
         file: "course/boa_parse_let_improperargs_fail4.snek",
-        expected: "Invalid",
+        expected: "E0005",
     },
     {
         name: boa_parse_let_improperargs_fail5,
         file: "course/boa_parse_let_improperargs_fail5.snek",
-        expected: "keyword",
+        expected: "E0009",
     },
 
     {
         name: boa_duplicate_binding_fail0,
         file: "course/boa_duplicate_binding_fail0.snek",
-        expected: "Duplicate binding",
+        expected: "E0012",
     },
     {
         name: boa_duplicate_binding_fail1,
         file: "course/boa_duplicate_binding_fail1.snek",
-        expected: "Duplicate binding",
+        expected: "E0012",
     },
     {
         name: boa_duplicate_binding_fail2,
         file: "course/boa_duplicate_binding_fail2.snek",
-        expected: "Duplicate binding",
+        expected: "E0012",
     },
 
     // Invalid if expressions
     {
         name: cobra_parse_if_fail0,
         file: "course/cobra_parse_if_fail0.snek",
-        expected: "Invalid",
+        expected: "E0005",
     },
     {
         name: cobra_parse_if_fail1,
         file: "course/cobra_parse_if_fail1.snek",
-        expected: "Invalid",
+        expected: "E0005",
     },
 
     // Unbound identifier
     {
         name: boa_unbound_identifier_fail0,
         file: "course/boa_unbound_identifier_fail0.snek",
-        expected: "Unbound variable identifier x",
+        expected: "E0013",
     },
     {
         name: boa_unbound_identifier_fail1,
         file: "course/boa_unbound_identifier_fail1.snek",
-        expected: "Unbound variable identifier y",
+        expected: "E0013",
     },
     {
         name: boa_unbound_identifier_fail2,
         file: "course/boa_unbound_identifier_fail2.snek",
-        expected: "Unbound variable identifier x",
+        expected: "E0013",
     },
     {
         name: cobra_unbound_identifier_fail3,
         file: "course/cobra_unbound_identifier_fail3.snek",
-        expected: "Unbound variable identifier z",
+        expected: "E0013",
     },
     {
         name: cobra_unbound_identifier_fail4,
         file: "course/cobra_unbound_identifier_fail4.snek",
-        expected: "Unbound variable identifier t",
+        expected: "E0013",
     },
     {
         name: cobra_unbound_identifier_fail5,
         file: "course/cobra_unbound_identifier_fail5.snek",
-        expected: "Unbound variable identifier x",
+        expected: "E0013",
     },
 
     // Invalid block
     {
         name: cobra_parse_block_fail0,
         file: "course/cobra_parse_block_fail0.snek",
-        expected: "Invalid",
+        expected: "E0005",
     },
 
     // Invalid break
     {
         name: cobra_invalid_break_fail0,
         file: "course/cobra_invalid_break_fail0.snek",
-        expected: "break",
+        expected: "E0017",
     },
 
     // Invalid loop
     {
         name: cobra_invalid_loop_fail0,
         file: "course/cobra_invalid_loop_fail0.snek",
-        expected: "Invalid",
+        expected: "E0005",
     },
     // Invalid function
     {
         name: diamondback_fun_duplicate_parameters_fail0,
         file: "course/diamondback_fun_duplicate_parameters_fail0.snek",
-        expected: "",
+        expected: "E0011",
     },
     {
         name: diamondback_fun_duplicate_parameters_fail1,
         file: "course/diamondback_fun_duplicate_parameters_fail1.snek",
-        expected: "",
+        expected: "E0011",
     },
     {
         name: diamondback_fun_input_fail0,
         file: "course/diamondback_fun_input_fail0.snek",
-        expected: "",
-    },
-    {
-        name: diamondback_fun_input_fail1,
-        file: "course/diamondback_fun_input_fail1.snek",
-        expected: "",
+        expected: "E0009",
     },
     {
         name: diamondback_fun_not_exists_fail,
         file: "course/diamondback_fun_not_exists_fail.snek",
-        expected: "",
+        expected: "E0014",
     },
     {
         name: diamondback_fun_wrong_numargs_fail,
         file: "course/diamondback_fun_wrong_numargs_fail.snek",
-        expected: "",
+        expected: "E0015",
     },
     {
         name: diamondback_fun_duplicate_names_fail,
         file: "course/diamondback_fun_duplicate_names_fail.snek",
-        expected: "",
+        expected: "E0010",
     },
 
     {
         name: diamondback_not_fun_fail0,
         file: "course/diamondback_not_fun_fail0.snek",
-        expected: "Invalid",
+        expected: "E0004",
     },
     {
         name: diamondback_not_fun_fail1,
         file: "course/diamondback_not_fun_fail1.snek",
-        expected: "Invalid",
+        expected: "E0004",
     },
     {
         name: diamondback_not_fun_fail2,
         file: "course/diamondback_not_fun_fail2.snek",
-        expected: "Invalid",
+        expected: "E0004",
     },
     {
         name: diamondback_not_fun_fail3,
         file: "course/diamondback_not_fun_fail3.snek",
-        expected: "Invalid",
+        expected: "E0003",
     },
     {
         name: diamondback_not_fun_fail4,
         file: "course/diamondback_not_fun_fail4.snek",
-        expected: "Invalid",
+        expected: "E0004",
     },
     {
         name: diamondback_not_fun_fail5,
         file: "course/diamondback_not_fun_fail5.snek",
-        expected: "",
+        expected: "E0004",
     },
 
     {
         name: diamondback_no_expr_fail,
         file: "course/diamondback_no_expr_fail.snek",
-        expected: "",
+        expected: "E0003",
     },
     {
         name: diamondback_nested_fun_fail,
         file: "course/diamondback_nested_fun_fail.snek",
-        expected: "",
+        expected: "E0001",
     },
 
     {
         name: diamondback_fun_scope_fail0,
         file: "course/diamondback_fun_scope_fail0.snek",
-        expected: "",
-    },
-    {
-        name: diamondback_fun_scope_fail1,
-        file: "course/diamondback_fun_scope_fail1.snek",
-        expected: "",
-    },
-    {
-        name: diamondback_fun_scope_fail2,
-        file: "course/diamondback_fun_scope_fail2.snek",
-        expected: "",
+        expected: "E0013",
     },
     {
         name: diamondback_fun_scope_fail3,
         file: "course/diamondback_fun_scope_fail3.snek",
-        expected: "",
+        expected: "E0013",
     },
 
     {
         name: diamondback_function_is_keyword_fail,
         file: "course/diamondback_function_is_keyword_fail.snek",
-        expected: "",
+        expected: "E0009",
     },
     {
         name: diamondback_function_arg_is_keyword_fail,
         file: "course/diamondback_function_arg_is_keyword_fail.snek",
-        expected: "",
+        expected: "E0009",
     },
 }
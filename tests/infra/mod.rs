@@ -7,6 +7,9 @@ pub(crate) enum TestType {
     Success,
     RuntimeError,
     StaticError,
+    JsonError,
+    Snippet,
+    Warning,
 }
 
 #[macro_export]
@@ -24,6 +27,33 @@ macro_rules! static_error_tests {
     ($($tt:tt)*) => { $crate::tests!(StaticError => $($tt)*); }
 }
 
+#[macro_export]
+macro_rules! json_error_tests {
+    ($($tt:tt)*) => { $crate::tests!(JsonError => $($tt)*); }
+}
+
+#[macro_export]
+macro_rules! snippet_tests {
+    ($($tt:tt)*) => { $crate::tests!(Snippet => $($tt)*); }
+}
+
+#[macro_export]
+macro_rules! warning_tests {
+    ($($tt:tt)*) => { $crate::tests!(Warning => $($tt)*); }
+}
+
+#[macro_export]
+macro_rules! explain_tests {
+    ($({ name: $name:ident, code: $code:literal, expected: $expected:literal $(,)? }),* $(,)?) => {
+        $(
+            #[test]
+            fn $name() {
+                $crate::infra::run_explain_test($code, $expected);
+            }
+        )*
+    };
+}
+
 #[macro_export]
 macro_rules! tests {
     ($kind:ident =>
@@ -31,7 +61,9 @@ macro_rules! tests {
             {
                 name: $name:ident,
                 file: $file:literal,
+                $(flags: [$($flag:literal),* $(,)?],)?
                 $(input: $input:literal,)?
+                $(args: [$($arg:literal),* $(,)?],)?
                 expected: $expected:literal $(,)?
                 $(" $(tt:$tt)* ")?
             }
@@ -42,10 +74,14 @@ macro_rules! tests {
             #[test]
             fn $name() {
                 #[allow(unused_assignments, unused_mut)]
-                let mut input = None;
-                $(input = Some($input);)?
+                let mut args: &[&str] = &[];
+                $(args = &[$input];)?
+                $(args = &[$($arg),*];)?
+                #[allow(unused_assignments, unused_mut)]
+                let mut flags: &[&str] = &[];
+                $(flags = &[$($flag),*];)?
                 let kind = $crate::infra::TestType::$kind;
-                $crate::infra::run_test(stringify!($name), $file, input, $expected, kind);
+                $crate::infra::run_test(stringify!($name), $file, flags, args, $expected, kind);
             }
         )*
     };
@@ -54,23 +90,44 @@ macro_rules! tests {
 pub(crate) fn run_test(
     name: &str,
     file: &str,
-    input: Option<&str>,
+    flags: &[&str],
+    args: &[&str],
     expected: &str,
     kind: TestType,
 ) {
     let file = Path::new("tests").join(file);
     match kind {
-        TestType::Success => run_success_test(name, &file, expected, input),
-        TestType::RuntimeError => run_runtime_error_test(name, &file, expected, input),
-        TestType::StaticError => run_static_error_test(name, &file, expected),
+        TestType::Success => run_success_test(name, &file, flags, expected, args),
+        TestType::RuntimeError => run_runtime_error_test(name, &file, flags, expected, args),
+        TestType::StaticError => run_static_error_test(name, &file, flags, expected),
+        TestType::JsonError => run_json_error_test(name, &file, flags, expected),
+        TestType::Snippet => run_snippet_test(name, &file, flags, expected),
+        TestType::Warning => run_warning_test(name, &file, flags, expected),
     }
 }
 
-fn run_success_test(name: &str, file: &Path, expected: &str, input: Option<&str>) {
-    if let Err(err) = compile(name, file) {
+pub(crate) fn run_explain_test(code: &str, expected: &str) {
+    let compiler: PathBuf = ["target", "debug", env!("CARGO_PKG_NAME")].iter().collect();
+    let output = Command::new(&compiler)
+        .arg("--explain")
+        .arg(code)
+        .output()
+        .expect("could not run the compiler");
+    if !output.status.success() {
+        panic!("expected an explanation, but got an error: `{}`", String::from_utf8(output.stderr).unwrap());
+    }
+    let explanation = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        explanation.contains(expected.trim()),
+        "the explanation does not contain `{expected}`:\n{explanation}",
+    );
+}
+
+fn run_success_test(name: &str, file: &Path, flags: &[&str], expected: &str, args: &[&str]) {
+    if let Err(err) = compile(name, file, flags) {
         panic!("expected a successful compilation, but got an error: `{err}`");
     }
-    match run(name, file, input) {
+    match run(name, file, args) {
         Err(err) => {
             panic!("expected a successful execution, but got an error: `{err}`");
         }
@@ -80,11 +137,11 @@ fn run_success_test(name: &str, file: &Path, expected: &str, input: Option<&str>
     }
 }
 
-fn run_runtime_error_test(name: &str, file: &Path, expected: &str, input: Option<&str>) {
-    if let Err(err) = compile(name, file) {
+fn run_runtime_error_test(name: &str, file: &Path, flags: &[&str], expected: &str, args: &[&str]) {
+    if let Err(err) = compile(name, file, flags) {
         panic!("expected a successful compilation, but got an error: `{err}`");
     }
-    match run(name, file, input) {
+    match run(name, file, args) {
         Ok(out) => {
             panic!("expected a runtime error, but program executed succesfully: `{out}`");
         }
@@ -92,41 +149,64 @@ fn run_runtime_error_test(name: &str, file: &Path, expected: &str, input: Option
     }
 }
 
-fn run_static_error_test(name: &str, file: &Path, expected: &str) {
-    match compile(name, file) {
-        Ok(()) => panic!("expected a failure, but compilation succeeded"),
-        Err(err) => check_error_msg(&err, expected),
+fn run_static_error_test(name: &str, file: &Path, flags: &[&str], expected: &str) {
+    match compile(name, file, flags) {
+        Ok(_) => panic!("expected a failure, but compilation succeeded"),
+        Err(err) => check_error_code(&err, expected),
     }
 }
 
-fn compile(name: &str, file: &Path) -> Result<(), String> {
+fn run_json_error_test(name: &str, file: &Path, flags: &[&str], expected: &str) {
+    let flags = [flags, &["--error-format=json"]].concat();
+    match compile(name, file, &flags) {
+        Ok(_) => panic!("expected a failure, but compilation succeeded"),
+        Err(err) => check_json_diagnostics(&err, expected),
+    }
+}
+
+fn run_snippet_test(name: &str, file: &Path, flags: &[&str], expected: &str) {
+    match compile(name, file, flags) {
+        Ok(_) => panic!("expected a failure, but compilation succeeded"),
+        Err(err) => check_snippet(&err, expected),
+    }
+}
+
+fn run_warning_test(name: &str, file: &Path, flags: &[&str], expected: &str) {
+    match compile(name, file, flags) {
+        Ok(warnings) => check_warning_code(&warnings, expected),
+        Err(err) => panic!("expected a successful compilation, but got an error: `{err}`"),
+    }
+}
+
+fn compile(name: &str, file: &Path, flags: &[&str]) -> Result<String, String> {
     // Run the compiler
     let compiler: PathBuf = ["target", "debug", env!("CARGO_PKG_NAME")].iter().collect();
     let output = Command::new(&compiler)
+        .args(flags)
         .arg(file)
-        .arg(&mk_path(file.parent().unwrap().to_str().unwrap(), name, Ext::Asm))
+        .arg(mk_path(file.parent().unwrap().to_str().unwrap(), name, Ext::Asm))
         .output()
         .expect("could not run the compiler");
     if !output.status.success() {
         return Err(String::from_utf8(output.stderr).unwrap());
     }
+    let warnings = String::from_utf8(output.stderr).unwrap();
 
     // Assemble and link
     let output = Command::new("make")
-        .arg(&mk_path(file.parent().unwrap().to_str().unwrap(), name, Ext::Run))
+        .arg(mk_path(file.parent().unwrap().to_str().unwrap(), name, Ext::Run))
         .output()
         .expect("could not run make");
     assert!(output.status.success(), "linking failed");
 
-    Ok(())
+    Ok(warnings)
 }
 
-fn run(name: &str, file: &Path, input: Option<&str>) -> Result<String, String> {
-    let mut cmd = Command::new(&mk_path(file.parent().unwrap().to_str().unwrap(), name, Ext::Run));
-    if let Some(input) = input {
-        cmd.arg(input);
-    }
-    let output = cmd.output().unwrap();
+fn run(name: &str, file: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(mk_path(file.parent().unwrap().to_str().unwrap(), name, Ext::Run))
+        .args(args)
+        .output()
+        .unwrap();
     if output.status.success() {
         Ok(String::from_utf8(output.stdout).unwrap().trim().to_string())
     } else {
@@ -141,6 +221,50 @@ fn check_error_msg(found: &str, expected: &str) {
     );
 }
 
+fn check_error_code(found: &str, expected: &str) {
+    let codes: Vec<&str> = found
+        .lines()
+        .filter_map(|line| line.strip_prefix("error[")?.split(']').next())
+        .collect();
+    assert!(
+        codes.contains(&expected.trim()),
+        "expected a `{expected}` error, but the compiler reported {codes:?}",
+    );
+}
+
+fn check_warning_code(found: &str, expected: &str) {
+    let codes: Vec<&str> = found
+        .lines()
+        .filter_map(|line| line.strip_prefix("warning[")?.split(']').next())
+        .collect();
+    assert!(
+        codes.contains(&expected.trim()),
+        "expected a `{expected}` warning, but the compiler reported {codes:?}",
+    );
+}
+
+fn check_json_diagnostics(found: &str, expected: &str) {
+    for line in found.lines() {
+        assert!(
+            line.starts_with('{') && line.ends_with('}'),
+            "expected one JSON object per line, but found `{line}`",
+        );
+    }
+    assert!(
+        found.lines().any(|line| line.contains(expected.trim())),
+        "no reported diagnostic contains `{expected}`:\n{found}",
+    );
+}
+
+fn check_snippet(found: &str, expected: &str) {
+    // Only the surrounding line breaks are insignificant, since snippets are aligned with spaces
+    let expected = expected.trim_matches('\n');
+    assert!(
+        found.contains(expected),
+        "the reported errors do not contain the snippet\n{expected}\nin:\n{found}",
+    );
+}
+
 fn diff(expected: &str, actual_output: String) {
     let expected_output = expected.trim();
     if expected_output != actual_output {